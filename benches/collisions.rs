@@ -0,0 +1,62 @@
+//! Time per update of a grid of moving circles, with the time of each phase of the step.
+//! Run it with `cargo bench --bench collisions`.
+//! The dense scenes are crowded: most bodies are in contact and many of them cover other cells each step.
+//! The calm scenes are sparse and slow: few bodies cover other cells each step.
+
+use torophy::{Space, Body, Shape::Circle, Vec2, Real};
+
+use std::time::{Duration, Instant};
+
+const WARMUP_UPDATES: u32 = 10;
+const UPDATES: u32 = 100;
+
+struct Scene {
+    name: &'static str,
+    bodies: usize,
+    side: Real,
+    radius: Real, // Fraction of the spacing of the grid
+    speed: Real,
+}
+
+const SCENES: [Scene; 4] = [
+    Scene { name: "dense", bodies: 5000, side: 350.0, radius: 0.6, speed: 5.0 },
+    Scene { name: "dense", bodies: 20000, side: 700.0, radius: 0.6, speed: 5.0 },
+    Scene { name: "calm", bodies: 5000, side: 350.0, radius: 0.4, speed: 1.0 },
+    Scene { name: "calm", bodies: 20000, side: 700.0, radius: 0.4, speed: 1.0 },
+];
+
+fn main() {
+    for scene in &SCENES {
+        let mut space = Space::new(scene.side, scene.side).with_optimization_cell_size(6.0);
+        let per_row = (scene.bodies as f64).sqrt().ceil() as usize;
+        let spacing = scene.side / per_row as Real;
+        for i in 0..scene.bodies {
+            let mut body = Body::new(Vec2::xy((i % per_row) as Real * spacing, (i / per_row) as Real * spacing));
+            body.set_shape(Circle(spacing * scene.radius));
+            body.set_velocity(Vec2::from_angle(i as Real) * scene.speed);
+            space.add(body);
+        }
+
+        for _ in 0..WARMUP_UPDATES {
+            space.update(Duration::from_millis(10));
+        }
+
+        let mut phases = [Duration::default(); 4];
+        let start = Instant::now();
+        for _ in 0..UPDATES {
+            space.update(Duration::from_millis(10));
+            let statistics = space.statistics();
+            phases[0] += statistics.integration_time;
+            phases[1] += statistics.broad_phase_time;
+            phases[2] += statistics.narrow_phase_time;
+            phases[3] += statistics.contact_resolution_time;
+        }
+        let milliseconds = |duration: Duration| duration.as_secs_f64() * 1000.0 / UPDATES as f64;
+
+        println!(
+            "{} {}: {:.3} ms per update (integration {:.3}, broad phase {:.3}, narrow phase {:.3}, resolution {:.3})",
+            scene.name, scene.bodies, milliseconds(start.elapsed()),
+            milliseconds(phases[0]), milliseconds(phases[1]), milliseconds(phases[2]), milliseconds(phases[3]),
+        );
+    }
+}
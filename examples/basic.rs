@@ -1,10 +1,9 @@
 use torophy::{Space, Body, Shape::Circle, Vec2};
-use std::time::Duration;
 
 mod monitor;
 
 fn main() {
-    let mut space = Space::new(800, 600);
+    let mut space = Space::new(800.0, 600.0);
 
     let mut body1 = Body::new(Vec2::xy(200.0, 200.0));
     body1.set_shape(Circle(60.0));
@@ -24,10 +23,6 @@ fn main() {
     body3.set_velocity(Vec2::xy(-100.0, 160.0));
     space.add(body3);
 
-    monitor::GraphicMonitor::new(space)
-        .main_loop(|space: &mut Space| {
-            space.update(Duration::from_secs_f32(0.0166)); //Real time simulation: 1 / 60 frames
-        }
-    );
+    monitor::GraphicMonitor::new(space).main_loop();
 }
 
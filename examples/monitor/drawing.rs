@@ -1,23 +1,30 @@
-use torophy::{Space, Shape};
+use torophy::{Space, Shape, Real, FixedStepper};
 use rand::{Rng, SeedableRng, rngs::StdRng};
 use std::time::{Duration};
 
 
 use super::renderer::{Renderer, Color};
 
-pub fn draw_space(renderer: &Renderer, target: &mut glium::Frame, space: &Space) {
+/// The renderer works with f32 values, whatever the precision of the engine.
+#[allow(clippy::unnecessary_cast)]
+pub fn to_f32(value: Real) -> f32 {
+    value as f32
+}
+
+/// Draws the bodies between their last two physics states, following the stepper.
+pub fn draw_space(renderer: &Renderer, target: &mut glium::Frame, space: &Space, stepper: &FixedStepper) {
     let mut rng = StdRng::seed_from_u64(1);
-    for body in space.bodies() {
-        let position = body.position();
+    for (body, position) in space.bodies().zip(stepper.interpolated_positions(space)) {
+        let position = (to_f32(position.x), to_f32(position.y));
         let color = Color::rgb(rng.gen_range(0.2, 0.8), rng.gen_range(0.2, 0.8), rng.gen_range(0.2, 0.8));
         match body.shape() {
             None => {
-                renderer.stroke_toroidal_circle(target, (position.x, position.y), 1.0, 4, color);
+                renderer.stroke_toroidal_circle(target, position, 1.0, 4, color);
             },
             Some(shape) => match shape {
                 Shape::Circle(radius) => {
                     let points = (radius * 2.0) as usize;
-                    renderer.stroke_toroidal_circle(target, (position.x, position.y), *radius, points, color);
+                    renderer.stroke_toroidal_circle(target, position, to_f32(*radius), points, color);
                 }
             },
         }
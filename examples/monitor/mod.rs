@@ -1,4 +1,4 @@
-use torophy::{Space};
+use torophy::{Space, FixedStepper};
 
 use glium::glutin;
 use glium::glutin::event::{Event, WindowEvent, DeviceEvent, VirtualKeyCode, ElementState};
@@ -12,6 +12,8 @@ use std::time::{Instant, Duration};
 mod drawing;
 mod renderer;
 
+const PHYSICS_STEP: Duration = Duration::from_micros(16_667); // 60 steps per second
+
 pub struct GraphicMonitor {
     display: glium::Display,
     event_loop: EventLoop<()>,
@@ -40,7 +42,7 @@ impl GraphicMonitor {
         let display = glium::Display::new(window_builder, glutin_context, &event_loop)
             .expect("Failed to initialize glium display");
 
-        let renderer = renderer::Renderer::new(&display, (dimension.x, dimension.y));
+        let renderer = renderer::Renderer::new(&display, (drawing::to_f32(dimension.x), drawing::to_f32(dimension.y)));
 
         let mut imgui = imgui::Context::create();
         imgui.set_ini_filename(None);
@@ -79,9 +81,9 @@ impl GraphicMonitor {
         }
     }
 
-    pub fn main_loop<F>(self, mut frame_action: F)
-    where F: 'static + FnMut(&mut Space) {
+    pub fn main_loop(self) {
         let GraphicMonitor { display, event_loop, renderer, mut imgui, mut imgui_renderer, mut imgui_platform, mut space } = self;
+        let mut stepper = FixedStepper::new(PHYSICS_STEP);
         let mut last_frame = Instant::now();
         let mut last_physics_time = Duration::from_secs(0);
         let mut ctrl_key = false;
@@ -89,8 +91,9 @@ impl GraphicMonitor {
             let gl_window = display.gl_window();
             match event {
                 Event::NewEvents(_) => {
-                    last_frame = imgui.io_mut().update_delta_time(last_frame);
-                    frame_action(&mut space);
+                    let now = imgui.io_mut().update_delta_time(last_frame);
+                    stepper.advance(&mut space, now - last_frame);
+                    last_frame = now;
                     last_physics_time = Instant::now() - last_frame;
                 },
                 Event::MainEventsCleared => {
@@ -102,7 +105,7 @@ impl GraphicMonitor {
                     let mut target = display.draw();
                     target.clear_color(0.0, 0.0, 0.0, 1.0);
 
-                    drawing::draw_space(&renderer, &mut target, &space);
+                    drawing::draw_space(&renderer, &mut target, &space, &stepper);
 
                     let mut imgui_ui = imgui.frame();
                     drawing::draw_ui(&mut imgui_ui, &space, last_physics_time);
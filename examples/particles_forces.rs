@@ -1,13 +1,13 @@
-use torophy::{Space, Body, Vec2};
-use std::time::Duration;
+use torophy::{Space, Body, Vec2, consts};
 use rand::{Rng, SeedableRng, rngs::StdRng, distributions::Uniform};
 
 mod monitor;
 
 fn main() {
-    let mut space = Space::new(800, 600);
+    let mut space = Space::new(800.0, 600.0);
+    space.add_force_field(|_: &Body, _: Vec2, _: Vec2| Vec2::xy(0.0, 15.0));
 
-    let angle_dist = Uniform::new(-std::f32::consts::PI, std::f32::consts::PI);
+    let angle_dist = Uniform::new(-consts::PI, consts::PI);
     let mass_dist = Uniform::new(1.0, 10.0);
     let speed_dist = Uniform::new(5.0, 30.0);
     let mut rng = StdRng::seed_from_u64(0);
@@ -19,13 +19,6 @@ fn main() {
         space.add(body);
     }
 
-    monitor::GraphicMonitor::new(space)
-        .main_loop(|space: &mut Space| {
-            for body in space.bodies_mut() {
-                body.add_force(Vec2::xy(0.0, 15.0));
-            }
-            space.update(Duration::from_secs_f32(0.0166)); //Real time simulation: 1 / 60 frames
-        }
-    );
+    monitor::GraphicMonitor::new(space).main_loop();
 }
 
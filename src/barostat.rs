@@ -0,0 +1,29 @@
+use super::math::{Real, functions::powf};
+
+/// Berendsen barostat: isotropically rescales the space to relax the measured pressure
+/// towards a target pressure.
+/// The pressure is measured with the virial expression, see [`crate::Space::pressure()`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Barostat {
+    pub target_pressure: Real,
+
+    /// Inverse of the bulk modulus of the simulated system.
+    /// Together with the relaxation time, it controls how fast the area reacts to the pressure error.
+    pub compressibility: Real,
+
+    /// Time constant (in seconds) of the pressure relaxation.
+    pub relaxation_time: Real,
+}
+
+impl Barostat {
+    pub fn new(target_pressure: Real, compressibility: Real, relaxation_time: Real) -> Barostat {
+        Barostat { target_pressure, compressibility, relaxation_time }
+    }
+
+    /// Factor to scale the lengths of a space of `D` dimensions after a step of dt with the measured pressure.
+    /// The volume (the area in 2D) change of a single step is limited to 10% to keep the rescaling stable.
+    pub fn length_scale<const D: usize>(&self, pressure: Real, dt: Real) -> Real {
+        let volume_scale = 1.0 - self.compressibility * dt / self.relaxation_time * (self.target_pressure - pressure);
+        powf(volume_scale.clamp(0.9, 1.1), 1.0 / D as Real)
+    }
+}
@@ -1,47 +1,59 @@
-use super::math::{Vec2, bounding::AxisAlignmentBoundingBox};
+use super::math::{Vector, Real, bounding::AxisAlignmentBoundingBox};
 use super::shapes::{Shape, Contact};
+use super::integrator::{Integrator, ForceField};
 
-pub trait Particle {
-    fn integrate(&mut self, dt: f32);
+pub trait Particle<const D: usize = 2> {
+    /// Acceleration of the particle at the given state.
+    /// It includes the applied force, the drag force and the force fields.
+    fn acceleration(&self, position: Vector<D>, velocity: Vector<D>, fields: &[Box<dyn ForceField<D>>]) -> Vector<D>;
+
+    /// Advances the particle by dt. The applied force is kept, so it can be integrated
+    /// along several substeps. The space clears it at the end of each update.
+    fn integrate(&mut self, dt: Real, integrator: Integrator, fields: &[Box<dyn ForceField<D>>]);
 }
 
-pub trait ContactResolver {
-    fn resolve_overlap(&self, b1: &mut Body, b2: &mut Body);
-    fn resolve_velocity(&self, b1: &mut Body, b2: &mut Body);
+pub trait ContactResolver<const D: usize = 2> {
+    fn resolve_overlap(&self, b1: &mut Body<D>, b2: &mut Body<D>);
+
+    /// Returns the magnitude of the impulse applied along the contact normal.
+    fn resolve_velocity(&self, b1: &mut Body<D>, b2: &mut Body<D>) -> Real;
 }
 
-pub struct Body {
+/// A body moving in a space of `D` dimensions, 2D by default.
+pub struct Body<const D: usize = 2> {
     shape: Option<Shape>,
-    position: Vec2,
-    inverse_mass: f32,
-    velocity: Vec2,
-    force: Vec2,
-    drag_force: (f32, f32),
-    restitution: f32,
+    position: Vector<D>,
+    inverse_mass: Real,
+    velocity: Vector<D>,
+    force: Vector<D>,
+    drag_force: (Real, Real),
+    restitution: Real,
+    winding: [i32; D],
 }
 
-impl Body {
-    pub fn new(position: Vec2) -> Body {
+impl<const D: usize> Body<D> {
+    pub fn new(position: Vector<D>) -> Body<D> {
         Body {
             shape: None,
             position,
             inverse_mass: 1.0,
-            velocity: Vec2::zero(),
-            force: Vec2::zero(),
+            velocity: Vector::zero(),
+            force: Vector::zero(),
             drag_force: (0.0, 0.0),
             restitution: 1.0,
+            winding: [0; D],
         }
     }
 
-    pub fn set_position(&mut self, position: Vec2) {
+    pub fn set_position(&mut self, position: Vector<D>) {
         self.position = position;
     }
 
-    pub fn displace(&mut self, displacement: Vec2) {
+    pub fn displace(&mut self, displacement: Vector<D>) {
         self.position += displacement;
     }
 
-    pub fn position(&self) -> Vec2 {
+    pub fn position(&self) -> Vector<D> {
         self.position
     }
 
@@ -57,88 +69,114 @@ impl Body {
         self.shape.as_ref()
     }
 
-    pub fn set_mass(&mut self, mass: f32) {
+    pub fn set_mass(&mut self, mass: Real) {
         self.inverse_mass = 1.0 / mass;
     }
 
-    pub fn add_mass(&mut self, mass: f32) {
+    pub fn add_mass(&mut self, mass: Real) {
         self.inverse_mass = 1.0 / (1.0 / self.inverse_mass + mass);
     }
 
-    pub fn mass(&self) -> f32 {
+    pub fn mass(&self) -> Real {
         1.0 / self.inverse_mass
     }
 
-    pub fn inverse_mass(&self) -> f32 {
+    pub fn inverse_mass(&self) -> Real {
         self.inverse_mass
     }
 
-    pub fn set_velocity(&mut self, velocity: Vec2) {
+    pub fn set_velocity(&mut self, velocity: Vector<D>) {
         self.velocity = velocity
     }
 
-    pub fn add_velocity(&mut self, velocity: Vec2) {
+    pub fn add_velocity(&mut self, velocity: Vector<D>) {
         self.velocity += velocity
     }
 
-    pub fn velocity(&self) -> Vec2 {
+    pub fn velocity(&self) -> Vector<D> {
         self.velocity
     }
 
-    pub fn set_force(&mut self, force: Vec2) {
+    pub fn set_force(&mut self, force: Vector<D>) {
         self.force = force;
     }
 
-    pub fn add_force(&mut self, force: Vec2) {
+    pub fn add_force(&mut self, force: Vector<D>) {
         self.force += force;
     }
 
-    pub fn force(&self) -> Vec2 {
+    pub fn force(&self) -> Vector<D> {
         self.force
     }
 
-    pub fn set_drag_force(&mut self, drag_force: (f32, f32)) {
+    pub fn set_drag_force(&mut self, drag_force: (Real, Real)) {
         self.drag_force = drag_force;
     }
 
-    pub fn drag_force(&self) -> (f32, f32) {
+    pub fn drag_force(&self) -> (Real, Real) {
         self.drag_force
     }
 
-    pub fn set_restitution(&mut self, restitution: f32) {
+    pub fn set_restitution(&mut self, restitution: Real) {
         self.restitution = restitution;
     }
 
-    pub fn restitution(&self) -> f32 {
+    pub fn restitution(&self) -> Real {
         self.restitution
     }
 
-    pub fn aabb(&self) -> Option<AxisAlignmentBoundingBox> {
+    /// Number of times the body turned around the space in each axis since it was added.
+    /// Positive values count the crossings of the right (or bottom) edge and negative ones the left (or top) edge.
+    pub fn winding(&self) -> [i32; D] {
+        self.winding
+    }
+
+    pub fn set_winding(&mut self, winding: [i32; D]) {
+        self.winding = winding;
+    }
+
+    /// Position that does not wrap around the edges of a space of the given dimension:
+    /// the position plus the winding times the dimension.
+    /// It is continuous along the trajectory of the body in toroidal spaces,
+    /// but not across the flipped or sheared edges of other topologies.
+    pub fn unwrapped_position(&self, dimension: Vector<D>) -> Vector<D> {
+        self.position + dimension.map(|axis, length| self.winding[axis] as Real * length)
+    }
+
+    pub fn aabb(&self) -> Option<AxisAlignmentBoundingBox<D>> {
         self.shape.as_ref().map(|shape| AxisAlignmentBoundingBox::new(self.position, shape.half_dimension()))
     }
 }
 
-impl Particle for Body {
-    fn integrate(&mut self, dt: f32) {
-        let real_force = self.force - self.velocity * (self.drag_force.0 + self.drag_force.1 * self.velocity.length());
-        self.velocity += real_force * (self.inverse_mass * dt);
+impl<const D: usize> Particle<D> for Body<D> {
+    fn acceleration(&self, position: Vector<D>, velocity: Vector<D>, fields: &[Box<dyn ForceField<D>>]) -> Vector<D> {
+        let drag = velocity * (self.drag_force.0 + self.drag_force.1 * velocity.length());
+        let field_force = fields.iter().fold(Vector::zero(), |total, field| total + field.force(self, position, velocity));
+        (self.force - drag + field_force) * self.inverse_mass
+    }
 
-        self.displace(self.velocity * dt);
-        self.force = Vec2::zero();
+    fn integrate(&mut self, dt: Real, integrator: Integrator, fields: &[Box<dyn ForceField<D>>]) {
+        let (position, velocity) = integrator.step(self.position, self.velocity, dt, |position, velocity| {
+            self.acceleration(position, velocity, fields)
+        });
+
+        self.position = position;
+        self.velocity = velocity;
     }
 }
 
-impl ContactResolver for Contact {
-    fn resolve_overlap(&self, b1: &mut Body, b2: &mut Body) {
+impl<const D: usize> ContactResolver<D> for Contact<D> {
+    fn resolve_overlap(&self, b1: &mut Body<D>, b2: &mut Body<D>) {
         let total_inverse_mass = b1.inverse_mass() + b2.inverse_mass();
         let displacement = self.normal() * (self.overlap() / total_inverse_mass);
 
         b1.displace(displacement * b1.inverse_mass());
-        b2.displace(displacement * -b2.inverse_mass());
+        b2.displace(self.orientation().apply(displacement * -b2.inverse_mass()));
     }
 
-    fn resolve_velocity(&self, b1: &mut Body, b2: &mut Body) {
-        let separating_speed = self.normal() * (b1.velocity() - b2.velocity());
+    fn resolve_velocity(&self, b1: &mut Body<D>, b2: &mut Body<D>) -> Real {
+        let image_velocity = self.orientation().apply(b2.velocity()) + self.velocity_shift();
+        let separating_speed = self.normal() * (b1.velocity() - image_velocity);
         if separating_speed < 0.0 {
             let new_separating_speed = -separating_speed * b1.restitution() * b2.restitution();
             let delta_speed  = new_separating_speed - separating_speed;
@@ -147,7 +185,9 @@ impl ContactResolver for Contact {
             let impulse = self.normal() * (delta_speed / total_inverse_mass);
 
             b1.add_velocity(impulse * b1.inverse_mass());
-            b2.add_velocity(impulse * -b2.inverse_mass());
+            b2.add_velocity(self.orientation().apply(impulse * -b2.inverse_mass()));
+            delta_speed / total_inverse_mass
         }
+        else { 0.0 }
     }
 }
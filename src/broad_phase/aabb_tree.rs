@@ -0,0 +1,231 @@
+use super::{BroadPhase, split_wrapped};
+use crate::math::{Vector, Real, bounding::AxisAlignmentBoundingBox};
+
+/// Fraction of the size of a region added at each side of its leaf box,
+/// so small movements do not change the tree.
+const MARGIN: Real = 0.1;
+
+const NONE: usize = usize::MAX;
+
+/// Dynamic bounding volume hierarchy: a binary tree whose leaves are the regions of the bodies,
+/// enlarged by a margin, and whose branches contain their children.
+/// A body is only moved in the tree when one of its regions leaves its enlarged box.
+/// The boxes straddling the edges of the space are split into one leaf for each side of the edges.
+pub struct AabbTree<const D: usize = 2> {
+    dimension: Vector<D>,
+    nodes: Vec<Node<D>>,
+    free_nodes: Vec<usize>,
+    root: usize,
+    leaves: Vec<Vec<usize>>, // leaf nodes of each body
+    regions: Vec<AxisAlignmentBoundingBox<D>>, // Stored for performance
+    stack: Vec<usize>, // Stored for performance
+    pairs: Vec<(usize, usize)>,
+}
+
+struct Node<const D: usize> {
+    aabb: AxisAlignmentBoundingBox<D>,
+    parent: usize,
+    content: Content<D>,
+}
+
+enum Content<const D: usize> {
+    Leaf { id: usize, region: AxisAlignmentBoundingBox<D> },
+    Branch([usize; 2]),
+}
+
+impl<const D: usize> AabbTree<D> {
+    pub fn new() -> AabbTree<D> {
+        AabbTree {
+            dimension: Vector::zero(),
+            nodes: Vec::new(),
+            free_nodes: Vec::new(),
+            root: NONE,
+            leaves: Vec::new(),
+            regions: Vec::new(),
+            stack: Vec::new(),
+            pairs: Vec::new(),
+        }
+    }
+
+    fn allocate(&mut self, node: Node<D>) -> usize {
+        match self.free_nodes.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            },
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            },
+        }
+    }
+
+    fn insert_leaf(&mut self, id: usize, region: AxisAlignmentBoundingBox<D>) -> usize {
+        let margin = region.dimension() * MARGIN;
+        let aabb = AxisAlignmentBoundingBox::from_corners(region.min() - margin, region.max() + margin);
+        let leaf = self.allocate(Node { aabb, parent: NONE, content: Content::Leaf { id, region } });
+        if self.root == NONE {
+            self.root = leaf;
+            return leaf
+        }
+
+        // The sibling is the node reached descending through the children that grow less.
+        let mut sibling = self.root;
+        while let Content::Branch(children) = self.nodes[sibling].content {
+            let growth = |child: usize| {
+                let child_aabb = &self.nodes[child].aabb;
+                measure(&child_aabb.union(&aabb)) - measure(child_aabb)
+            };
+            sibling = if growth(children[0]) <= growth(children[1]) { children[0] } else { children[1] };
+        }
+
+        let old_parent = self.nodes[sibling].parent;
+        let parent = self.allocate(Node {
+            aabb: self.nodes[sibling].aabb.union(&aabb),
+            parent: old_parent,
+            content: Content::Branch([sibling, leaf]),
+        });
+        self.nodes[sibling].parent = parent;
+        self.nodes[leaf].parent = parent;
+        if old_parent == NONE {
+            self.root = parent;
+        }
+        else {
+            self.replace_child(old_parent, sibling, parent);
+            self.refit(old_parent);
+        }
+        leaf
+    }
+
+    fn remove_leaf(&mut self, leaf: usize) {
+        self.free_nodes.push(leaf);
+        let parent = self.nodes[leaf].parent;
+        if parent == NONE {
+            self.root = NONE;
+            return
+        }
+
+        let sibling = match self.nodes[parent].content {
+            Content::Branch([first, second]) => if first == leaf { second } else { first },
+            Content::Leaf { .. } => unreachable!("The parent of a node is a branch"),
+        };
+        let grandparent = self.nodes[parent].parent;
+        self.nodes[sibling].parent = grandparent;
+        self.free_nodes.push(parent);
+        if grandparent == NONE {
+            self.root = sibling;
+        }
+        else {
+            self.replace_child(grandparent, parent, sibling);
+            self.refit(grandparent);
+        }
+    }
+
+    fn replace_child(&mut self, parent: usize, old_child: usize, new_child: usize) {
+        if let Content::Branch(children) = &mut self.nodes[parent].content {
+            for child in children.iter_mut().filter(|child| **child == old_child) {
+                *child = new_child;
+            }
+        }
+    }
+
+    /// Recomputes the boxes of the branch and its ancestors.
+    fn refit(&mut self, mut branch: usize) {
+        while branch != NONE {
+            if let Content::Branch([first, second]) = self.nodes[branch].content {
+                self.nodes[branch].aabb = self.nodes[first].aabb.union(&self.nodes[second].aabb);
+            }
+            branch = self.nodes[branch].parent;
+        }
+    }
+}
+
+impl<const D: usize> Default for AabbTree<D> {
+    fn default() -> Self {
+        AabbTree::new()
+    }
+}
+
+impl<const D: usize> BroadPhase<D> for AabbTree<D> {
+    fn resize(&mut self, dimension: Vector<D>) {
+        self.dimension = dimension;
+        self.clear();
+    }
+
+    fn clear(&mut self) {
+        self.nodes.clear();
+        self.free_nodes.clear();
+        self.root = NONE;
+        self.leaves.clear();
+        self.pairs.clear();
+    }
+
+    fn update(&mut self, id: usize, aabbs: &[AxisAlignmentBoundingBox<D>]) {
+        if id >= self.leaves.len() {
+            self.leaves.resize_with(id + 1, Vec::new);
+        }
+        let mut regions = std::mem::take(&mut self.regions);
+        regions.clear();
+        for aabb in aabbs {
+            split_wrapped(aabb, self.dimension, &mut regions);
+        }
+
+        let leaves = std::mem::take(&mut self.leaves[id]);
+        let fits = leaves.len() == regions.len()
+            && leaves.iter().zip(&regions).all(|(leaf, region)| self.nodes[*leaf].aabb.contains(region));
+        if fits {
+            for (leaf, region) in leaves.iter().zip(&regions) {
+                self.nodes[*leaf].content = Content::Leaf { id, region: *region };
+            }
+            self.leaves[id] = leaves;
+        }
+        else {
+            for leaf in leaves {
+                self.remove_leaf(leaf);
+            }
+            for region in &regions {
+                let leaf = self.insert_leaf(id, *region);
+                self.leaves[id].push(leaf);
+            }
+        }
+        self.regions = regions;
+    }
+
+    fn pairs(&mut self) -> &[(usize, usize)] {
+        self.pairs.clear();
+        for leaves in &self.leaves {
+            for &leaf in leaves {
+                let (id, region) = match self.nodes[leaf].content {
+                    Content::Leaf { id, region } => (id, region),
+                    Content::Branch(_) => unreachable!("The leaves of a body are leaf nodes"),
+                };
+
+                // Each pair is found from the body with the higher id.
+                self.stack.clear();
+                self.stack.push(self.root);
+                while let Some(index) = self.stack.pop() {
+                    let node = &self.nodes[index];
+                    if !node.aabb.intersects(&region) {
+                        continue
+                    }
+                    match node.content {
+                        Content::Leaf { id: other_id, region: other_region } => {
+                            if other_id < id && region.intersects(&other_region) {
+                                self.pairs.push((id, other_id));
+                            }
+                        },
+                        Content::Branch(children) => self.stack.extend_from_slice(&children),
+                    }
+                }
+            }
+        }
+        self.pairs.sort_unstable();
+        self.pairs.dedup();
+        &self.pairs
+    }
+}
+
+/// Sum of the sides of the box: a cost that, unlike the volume, grows with flat boxes.
+fn measure<const D: usize>(aabb: &AxisAlignmentBoundingBox<D>) -> Real {
+    aabb.dimension().components().iter().sum()
+}
@@ -0,0 +1,219 @@
+use super::{BroadPhase, GridOccupancy, split_wrapped};
+use crate::math::{Vector, Real, bounding::AxisAlignmentBoundingBox, functions::{powf, log}};
+
+/// Ratio between the cell sizes of consecutive levels.
+const LEVEL_RATIO: Real = 2.0;
+
+/// Maximum number of cells of a level for each region in the grid,
+/// to avoid allocating huge levels when there are very small regions.
+const CELLS_PER_REGION: Real = 4.0;
+
+/// Stack of uniform grids whose cell sizes double from one level to the next.
+/// Each region goes to the level whose cells are as large as the region,
+/// so scenes mixing very large and very small bodies do not need a compromise cell size.
+/// The levels are chosen every step from the sizes of the regions:
+/// the finest level fits the smallest region and the coarsest one the largest region.
+/// Each region looks for overlaps in its own level and in the coarser ones;
+/// the overlaps with finer regions are found by those regions.
+pub struct HierarchicalGrid<const D: usize = 2> {
+    dimension: Vector<D>,
+    regions: Vec<Vec<AxisAlignmentBoundingBox<D>>>, // regions of each body, already split
+    pieces: Vec<Piece<D>>,
+    levels: Vec<Level<D>>,
+    covered: Vec<usize>, // Stored for performance
+    pairs: Vec<(usize, usize)>,
+}
+
+struct Piece<const D: usize> {
+    aabb: AxisAlignmentBoundingBox<D>,
+    id: usize,
+    level: usize,
+}
+
+struct Level<const D: usize> {
+    cell_size: Real,
+    cell_counts: [usize; D],
+    cells: Vec<Vec<usize>>, // pieces in each cell
+    occupied: bool,
+}
+
+impl<const D: usize> Level<D> {
+    fn new(dimension: Vector<D>, cell_size: Real) -> Level<D> {
+        let mut cell_counts = [1; D];
+        for (axis, count) in cell_counts.iter_mut().enumerate() {
+            *count = ((dimension[axis] / cell_size).ceil() as usize).max(1);
+        }
+        Level {
+            cell_size,
+            cell_counts,
+            cells: (0..cell_counts.iter().product()).map(|_| Vec::new()).collect(),
+            occupied: false,
+        }
+    }
+
+    /// Pushes the index of every cell covered by the AABB, that must not wrap.
+    fn covered_cells(&self, aabb: &AxisAlignmentBoundingBox<D>, output: &mut Vec<usize>) {
+        output.clear();
+        let mut first = [0; D];
+        let mut last = [0; D];
+        for axis in 0..D {
+            let cell = |coordinate: Real| ((coordinate / self.cell_size) as usize).min(self.cell_counts[axis] - 1);
+            first[axis] = cell(aabb.min()[axis]);
+            last[axis] = cell(aabb.max()[axis]);
+        }
+
+        let mut coordinates = first;
+        'cells: loop {
+            let index = (0..D).fold(0, |index, axis| index * self.cell_counts[axis] + coordinates[axis]);
+            output.push(index);
+
+            for axis in (0..D).rev() {
+                if coordinates[axis] < last[axis] {
+                    coordinates[axis] += 1;
+                    continue 'cells
+                }
+                coordinates[axis] = first[axis];
+            }
+            break
+        }
+    }
+}
+
+impl<const D: usize> HierarchicalGrid<D> {
+    pub fn new() -> HierarchicalGrid<D> {
+        HierarchicalGrid {
+            dimension: Vector::zero(),
+            regions: Vec::new(),
+            pieces: Vec::new(),
+            levels: Vec::new(),
+            covered: Vec::new(),
+            pairs: Vec::new(),
+        }
+    }
+
+    /// Number of levels used in the last step.
+    pub fn level_count(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Chooses the levels for the current pieces and puts each piece in its cells.
+    fn build_levels(&mut self) {
+        let size = |aabb: &AxisAlignmentBoundingBox<D>| aabb.dimension().components().iter().fold(0.0 as Real, |a, b| a.max(*b));
+        let (min_size, max_size) = self.pieces.iter().fold((Real::INFINITY, 0.0 as Real), |(min, max), piece| {
+            let size = size(&piece.aabb);
+            (min.min(size), max.max(size))
+        });
+        let sparse_size = powf(self.dimension.product() / (CELLS_PER_REGION * self.pieces.len() as Real), 1.0 / D as Real);
+        let finest = min_size.max(sparse_size).max(Real::EPSILON);
+        let level_count = 1 + log(max_size / finest, LEVEL_RATIO).ceil().max(0.0) as usize;
+
+        self.levels.truncate(level_count);
+        for level in 0..level_count {
+            let cell_size = (0..level).fold(finest, |size, _| size * LEVEL_RATIO); // exact, unlike powi
+            match self.levels.get_mut(level) {
+                Some(existing) if existing.cell_size == cell_size => {
+                    existing.cells.iter_mut().for_each(Vec::clear);
+                    existing.occupied = false;
+                },
+                Some(existing) => *existing = Level::new(self.dimension, cell_size),
+                None => self.levels.push(Level::new(self.dimension, cell_size)),
+            }
+        }
+
+        for (index, piece) in self.pieces.iter_mut().enumerate() {
+            piece.level = self.levels.iter().position(|level| level.cell_size >= size(&piece.aabb)).unwrap_or(level_count - 1);
+            let level = &mut self.levels[piece.level];
+            level.occupied = true;
+            level.covered_cells(&piece.aabb, &mut self.covered);
+            for &cell in &self.covered {
+                level.cells[cell].push(index);
+            }
+        }
+    }
+}
+
+impl<const D: usize> Default for HierarchicalGrid<D> {
+    fn default() -> Self {
+        HierarchicalGrid::new()
+    }
+}
+
+impl<const D: usize> BroadPhase<D> for HierarchicalGrid<D> {
+    fn resize(&mut self, dimension: Vector<D>) {
+        self.dimension = dimension;
+        self.levels.clear();
+        self.clear();
+    }
+
+    fn clear(&mut self) {
+        self.regions.clear();
+        self.pairs.clear();
+    }
+
+    fn update(&mut self, id: usize, aabbs: &[AxisAlignmentBoundingBox<D>]) {
+        if id >= self.regions.len() {
+            self.regions.resize_with(id + 1, Vec::new);
+        }
+        let regions = &mut self.regions[id];
+        regions.clear();
+        for aabb in aabbs {
+            split_wrapped(aabb, self.dimension, regions);
+        }
+    }
+
+    fn pairs(&mut self) -> &[(usize, usize)] {
+        self.pieces.clear();
+        for (id, regions) in self.regions.iter().enumerate() {
+            self.pieces.extend(regions.iter().map(|aabb| Piece { aabb: *aabb, id, level: 0 }));
+        }
+        self.pairs.clear();
+        if self.pieces.is_empty() {
+            return &self.pairs
+        }
+        self.build_levels();
+
+        let pieces = &self.pieces;
+        for piece in pieces {
+            for level in self.levels[piece.level..].iter().filter(|level| level.occupied) {
+                level.covered_cells(&piece.aabb, &mut self.covered);
+                for &cell in &self.covered {
+                    for other in level.cells[cell].iter().map(|&other| &pieces[other]) {
+                        if other.id != piece.id && piece.aabb.intersects(&other.aabb) {
+                            self.pairs.push((piece.id.max(other.id), piece.id.min(other.id)));
+                        }
+                    }
+                }
+            }
+        }
+        self.pairs.sort_unstable();
+        self.pairs.dedup();
+        &self.pairs
+    }
+
+    /// Cells of every level. A body split by the edges, or covering several cells, counts in each one.
+    fn occupancy(&self) -> Option<GridOccupancy> {
+        Some(GridOccupancy::from_cell_sizes(self.levels.iter().flat_map(|level| level.cells.iter().map(Vec::len))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Vec2;
+
+    #[test]
+    fn planet_and_pebbles_use_several_levels() {
+        let mut grid = HierarchicalGrid::new();
+        grid.resize(Vec2::xy(1000.0, 1000.0));
+        grid.update(0, &[AxisAlignmentBoundingBox::new(Vec2::xy(500.0, 500.0), Vec2::xy(100.0, 100.0))]);
+        for id in 1..=1000 {
+            let position = Vec2::xy((id % 40) as Real * 25.0, (id / 40) as Real * 25.0 + 1.0);
+            grid.update(id, &[AxisAlignmentBoundingBox::new(position, Vec2::xy(1.0, 1.0))]);
+        }
+
+        let pairs = grid.pairs().to_vec();
+        assert!(grid.level_count() > 3, "levels: {}", grid.level_count());
+        assert!(!pairs.is_empty());
+        assert!(pairs.iter().all(|&(_, second)| second == 0));
+    }
+}
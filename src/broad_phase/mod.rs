@@ -0,0 +1,155 @@
+//! Algorithms that find the pairs of bodies that may collide, before the exact (narrow phase) check.
+//! [`SpatialTable`] is used by default. [`SweepAndPrune`], [`AabbTree`] and [`HierarchicalGrid`]
+//! do not need a cell size and behave better when the sizes of the bodies vary widely.
+
+mod sweep_and_prune;
+mod aabb_tree;
+mod hierarchical_grid;
+
+pub use super::spatial_table::SpatialTable;
+pub use sweep_and_prune::SweepAndPrune;
+pub use aabb_tree::AabbTree;
+pub use hierarchical_grid::HierarchicalGrid;
+
+use super::math::{Vector, Real, bounding::AxisAlignmentBoundingBox};
+
+/// Broad phase of the collision detection used by a [`crate::Space`].
+/// The space gives the regions covered by each body, identified by its index,
+/// and asks for the pairs of bodies whose regions may overlap.
+pub trait BroadPhase<const D: usize = 2> {
+    /// Adapts to a new dimension of the space, removing every body.
+    /// The space calls it before the first update and every time it is resized.
+    fn resize(&mut self, dimension: Vector<D>);
+
+    /// Removes every body. The space calls it when the indices of the bodies change.
+    fn clear(&mut self);
+
+    /// Sets the regions covered by a body, in grid coordinates (see [`crate::toroidal::Bounds::get_grid_aabb()`]).
+    /// A region whose minimum coordinate is greater than the maximum one wraps around that axis
+    /// (see [`crate::toroidal::Bounds::get_toroidal_aabb()`]).
+    /// A body without regions does not collide.
+    fn update(&mut self, id: usize, aabbs: &[AxisAlignmentBoundingBox<D>]);
+
+    /// Pairs of bodies that may overlap, without repetitions. The higher id of the pair goes first.
+    /// The order must only depend on the updates, so the simulations are reproducible.
+    fn pairs(&mut self) -> &[(usize, usize)];
+
+    /// Size of the cells, for the broad phases based on a grid.
+    fn cell_size(&self) -> Option<Real> {
+        None
+    }
+
+    /// Occupation of the cells, for the broad phases based on a grid.
+    fn occupancy(&self) -> Option<GridOccupancy> {
+        None
+    }
+}
+
+/// Occupation of the cells of a broad phase based on a grid.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GridOccupancy {
+    /// Cells with at least one body.
+    pub occupied_cells: usize,
+
+    /// Number of bodies in the most crowded cell.
+    pub max_bodies_per_cell: usize,
+}
+
+impl GridOccupancy {
+    pub(crate) fn from_cell_sizes(sizes: impl Iterator<Item = usize>) -> GridOccupancy {
+        sizes.filter(|size| *size > 0).fold(GridOccupancy::default(), |occupancy, size| GridOccupancy {
+            occupied_cells: occupancy.occupied_cells + 1,
+            max_bodies_per_cell: occupancy.max_bodies_per_cell.max(size),
+        })
+    }
+}
+
+/// Splits an AABB that may wrap around the axes of a space of the given dimension into
+/// the AABBs, inside the space, that do not wrap.
+fn split_wrapped<const D: usize>(aabb: &AxisAlignmentBoundingBox<D>, dimension: Vector<D>, output: &mut Vec<AxisAlignmentBoundingBox<D>>) {
+    let (min, max) = (aabb.min(), aabb.max());
+    let (mut wraps, mut wrap_count) = ([0; D], 0);
+    for axis in (0..D).filter(|&axis| min[axis] > max[axis]) {
+        wraps[wrap_count] = axis;
+        wrap_count += 1;
+    }
+    for pieces in 0..1usize << wrap_count {
+        let (mut piece_min, mut piece_max) = (min, max);
+        for (bit, &axis) in wraps[..wrap_count].iter().enumerate() {
+            if pieces & (1 << bit) == 0 {
+                piece_max[axis] = dimension[axis];
+            }
+            else {
+                piece_min[axis] = 0.0;
+            }
+        }
+        output.push(AxisAlignmentBoundingBox::from_corners(piece_min, piece_max));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::math::Vec2;
+
+    /// Wrapped boxes of several sizes, some of them across the edges of a 100 x 50 space.
+    fn scene() -> Vec<AxisAlignmentBoundingBox> {
+        (0..60).map(|i| {
+            let position = Vec2::xy((i * 37 % 100) as Real, (i * 13 % 50) as Real);
+            let half = if i % 10 == 0 { 12.0 } else { 1.0 + (i % 4) as Real };
+            let aabb = AxisAlignmentBoundingBox::new(position, Vec2::xy(half, half));
+            let wrap = |coordinate: Real, length: Real| coordinate.rem_euclid(length);
+            AxisAlignmentBoundingBox::from_corners(
+                Vec2::xy(wrap(aabb.min().x, 100.0), wrap(aabb.min().y, 50.0)),
+                Vec2::xy(wrap(aabb.max().x, 100.0), wrap(aabb.max().y, 50.0)),
+            )
+        }).collect()
+    }
+
+    fn find_pairs<B: BroadPhase>(mut broad_phase: B, aabbs: &[AxisAlignmentBoundingBox]) -> Vec<(usize, usize)> {
+        broad_phase.resize(Vec2::xy(100.0, 50.0));
+        for (id, aabb) in aabbs.iter().enumerate() {
+            broad_phase.update(id, &[*aabb]);
+        }
+        broad_phase.pairs().to_vec()
+    }
+
+    #[test]
+    fn broad_phases_find_every_overlap() {
+        let aabbs = scene();
+        let mut expected = Vec::new();
+        let mut pieces = (Vec::new(), Vec::new());
+        for (first, a) in aabbs.iter().enumerate() {
+            for (second, b) in aabbs.iter().enumerate().take(first) {
+                pieces.0.clear();
+                pieces.1.clear();
+                split_wrapped(a, Vec2::xy(100.0, 50.0), &mut pieces.0);
+                split_wrapped(b, Vec2::xy(100.0, 50.0), &mut pieces.1);
+                if pieces.0.iter().any(|a| pieces.1.iter().any(|b| a.intersects(b))) {
+                    expected.push((first, second));
+                }
+            }
+        }
+        expected.sort_unstable();
+        assert!(!expected.is_empty());
+
+        assert_eq!(find_pairs(SweepAndPrune::new(), &aabbs), expected);
+        assert_eq!(find_pairs(AabbTree::new(), &aabbs), expected);
+        assert_eq!(find_pairs(HierarchicalGrid::new(), &aabbs), expected);
+        let grid = find_pairs(SpatialTable::new(Vec2::xy(100.0, 50.0), 10.0), &aabbs);
+        assert!(expected.iter().all(|pair| grid.contains(pair)));
+    }
+
+    #[test]
+    fn split_across_both_axes() {
+        let aabb = AxisAlignmentBoundingBox::from_corners(Vec2::xy(90.0, 45.0), Vec2::xy(5.0, 2.0));
+        let mut pieces = Vec::new();
+        split_wrapped(&aabb, Vec2::xy(100.0, 50.0), &mut pieces);
+        assert_eq!(pieces, vec![
+            AxisAlignmentBoundingBox::from_corners(Vec2::xy(90.0, 45.0), Vec2::xy(100.0, 50.0)),
+            AxisAlignmentBoundingBox::from_corners(Vec2::xy(0.0, 45.0), Vec2::xy(5.0, 50.0)),
+            AxisAlignmentBoundingBox::from_corners(Vec2::xy(90.0, 0.0), Vec2::xy(100.0, 2.0)),
+            AxisAlignmentBoundingBox::from_corners(Vec2::xy(0.0, 0.0), Vec2::xy(5.0, 2.0)),
+        ]);
+    }
+}
@@ -0,0 +1,97 @@
+use super::{BroadPhase, split_wrapped};
+use crate::math::{Vector, Real, bounding::AxisAlignmentBoundingBox};
+
+/// Sorts the boxes along one axis and sweeps them, only checking the boxes whose intervals
+/// overlap along that axis. The sweep axis is the one where the boxes are more spread.
+/// The boxes wrapping around the edges are split into the parts at each side of the edges,
+/// so the intervals to sort never wrap.
+pub struct SweepAndPrune<const D: usize = 2> {
+    dimension: Vector<D>,
+    regions: Vec<Vec<AxisAlignmentBoundingBox<D>>>, // regions of each body, already split
+    pieces: Vec<(AxisAlignmentBoundingBox<D>, usize)>, // Stored for performance
+    pairs: Vec<(usize, usize)>,
+}
+
+impl<const D: usize> SweepAndPrune<D> {
+    pub fn new() -> SweepAndPrune<D> {
+        SweepAndPrune {
+            dimension: Vector::zero(),
+            regions: Vec::new(),
+            pieces: Vec::new(),
+            pairs: Vec::new(),
+        }
+    }
+
+    /// Axis with the greatest variance of the centers of the pieces.
+    fn sweep_axis(&self) -> usize {
+        let count = self.pieces.len().max(1) as Real;
+        let mut sum = Vector::<D>::zero();
+        let mut square_sum = Vector::<D>::zero();
+        for (aabb, _) in &self.pieces {
+            let center = aabb.position();
+            sum += center;
+            square_sum += center.map(|_, component| component * component);
+        }
+        let variance = |axis: usize| {
+            let mean = sum[axis] / count;
+            square_sum[axis] / count - mean * mean // powi may not give the same bits on every platform
+        };
+        (0..D).fold(0, |best, axis| if variance(axis) > variance(best) { axis } else { best })
+    }
+}
+
+impl<const D: usize> Default for SweepAndPrune<D> {
+    fn default() -> Self {
+        SweepAndPrune::new()
+    }
+}
+
+impl<const D: usize> BroadPhase<D> for SweepAndPrune<D> {
+    fn resize(&mut self, dimension: Vector<D>) {
+        self.dimension = dimension;
+        self.clear();
+    }
+
+    fn clear(&mut self) {
+        self.regions.clear();
+        self.pairs.clear();
+    }
+
+    fn update(&mut self, id: usize, aabbs: &[AxisAlignmentBoundingBox<D>]) {
+        if id >= self.regions.len() {
+            self.regions.resize_with(id + 1, Vec::new);
+        }
+        let regions = &mut self.regions[id];
+        regions.clear();
+        for aabb in aabbs {
+            split_wrapped(aabb, self.dimension, regions);
+        }
+    }
+
+    fn pairs(&mut self) -> &[(usize, usize)] {
+        self.pieces.clear();
+        for (id, regions) in self.regions.iter().enumerate() {
+            self.pieces.extend(regions.iter().map(|aabb| (*aabb, id)));
+        }
+
+        let axis = self.sweep_axis();
+        self.pieces.sort_unstable_by(|(a, a_id), (b, b_id)| {
+            a.min()[axis].total_cmp(&b.min()[axis]).then(a_id.cmp(b_id))
+        });
+
+        self.pairs.clear();
+        for (index, (aabb, id)) in self.pieces.iter().enumerate() {
+            for (other, other_id) in &self.pieces[index + 1..] {
+                if other.min()[axis] > aabb.max()[axis] {
+                    break
+                }
+                if id != other_id && aabb.intersects(other) {
+                    self.pairs.push((*id.max(other_id), *id.min(other_id)));
+                }
+            }
+        }
+        self.pairs.sort_unstable();
+        self.pairs.dedup();
+        &self.pairs
+    }
+}
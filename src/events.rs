@@ -0,0 +1,49 @@
+use super::math::Vector;
+use super::space::BodyHandle;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    /// Axis of the given index: 0 for x, 1 for y and 2 for z.
+    pub fn from_index(index: usize) -> Axis {
+        match index {
+            0 => Axis::X,
+            1 => Axis::Y,
+            2 => Axis::Z,
+            _ => panic!("There is no named axis with index {}", index),
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// The body left by the right (or bottom) edge.
+    Positive,
+
+    /// The body left by the left (or top) edge.
+    Negative,
+}
+
+/// A body wrapped around a pair of glued edges of the space during an update.
+/// It is reported whatever moved the body across the edge: the integration or the collision resolution.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SeamCrossing<const D: usize = 2> {
+    pub body: BodyHandle,
+    pub axis: Axis,
+    pub direction: Direction,
+
+    /// Position of the body outside of the space, just before wrapping it.
+    pub position_before: Vector<D>,
+
+    /// Position of the body inside the space, after wrapping it.
+    pub position_after: Vector<D>,
+}
@@ -0,0 +1,120 @@
+use super::math::{Vector, Real, functions::cbrt};
+use super::body::Body;
+use super::parallel::Shareable;
+
+/// Force that depends on the state of a body.
+/// Multi-stage integrators evaluate it several times per step, with the intermediate
+/// position and velocity of the body. The intermediate positions are not wrapped
+/// into the space bounds.
+/// With the `parallel` feature, force fields are evaluated from several threads and must be `Send + Sync`.
+pub trait ForceField<const D: usize = 2>: Shareable {
+    fn force(&self, body: &Body<D>, position: Vector<D>, velocity: Vector<D>) -> Vector<D>;
+}
+
+impl<F, const D: usize> ForceField<D> for F
+where F: Fn(&Body<D>, Vector<D>, Vector<D>) -> Vector<D> + Shareable {
+    fn force(&self, body: &Body<D>, position: Vector<D>, velocity: Vector<D>) -> Vector<D> {
+        self(body, position, velocity)
+    }
+}
+
+/// Numerical scheme used to advance the position and velocity of the bodies.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum Integrator {
+    /// First order. Updates the velocity and then the position with the new velocity.
+    /// One force evaluation per step.
+    #[default]
+    SemiImplicitEuler,
+
+    /// Second order and symplectic. Two force evaluations per step.
+    VelocityVerlet,
+
+    /// Classic fourth order Runge-Kutta. Accurate but not symplectic:
+    /// the energy slowly drifts in long conservative runs. Four force evaluations per step.
+    RungeKutta4,
+
+    /// Fourth order symplectic scheme built from three velocity Verlet stages (Yoshida 1990).
+    /// Three force evaluations per step.
+    Yoshida4,
+}
+
+impl Integrator {
+    /// Advances the state (position, velocity) by dt.
+    /// The acceleration function is evaluated at every intermediate stage of the scheme.
+    pub fn step<A, const D: usize>(&self, position: Vector<D>, velocity: Vector<D>, dt: Real, acceleration: A) -> (Vector<D>, Vector<D>)
+    where A: Fn(Vector<D>, Vector<D>) -> Vector<D> {
+        match *self {
+            Integrator::SemiImplicitEuler => {
+                let velocity = velocity + acceleration(position, velocity) * dt;
+                (position + velocity * dt, velocity)
+            },
+            Integrator::VelocityVerlet => {
+                let half_velocity = velocity + acceleration(position, velocity) * (dt / 2.0);
+                let position = position + half_velocity * dt;
+                (position, half_velocity + acceleration(position, half_velocity) * (dt / 2.0))
+            },
+            Integrator::RungeKutta4 => {
+                let (k1_x, k1_v) = (velocity, acceleration(position, velocity));
+                let (x2, v2) = (position + k1_x * (dt / 2.0), velocity + k1_v * (dt / 2.0));
+                let (k2_x, k2_v) = (v2, acceleration(x2, v2));
+                let (x3, v3) = (position + k2_x * (dt / 2.0), velocity + k2_v * (dt / 2.0));
+                let (k3_x, k3_v) = (v3, acceleration(x3, v3));
+                let (x4, v4) = (position + k3_x * dt, velocity + k3_v * dt);
+                let (k4_x, k4_v) = (v4, acceleration(x4, v4));
+                (
+                    position + (k1_x + k2_x * 2.0 + k3_x * 2.0 + k4_x) * (dt / 6.0),
+                    velocity + (k1_v + k2_v * 2.0 + k3_v * 2.0 + k4_v) * (dt / 6.0),
+                )
+            },
+            Integrator::Yoshida4 => {
+                let cbrt_2 = cbrt(2.0);
+                let w1 = 1.0 / (2.0 - cbrt_2);
+                let w0 = -cbrt_2 * w1;
+                let drifts = [w1 / 2.0, (w0 + w1) / 2.0, (w0 + w1) / 2.0, w1 / 2.0];
+                let kicks = [w1, w0, w1];
+
+                let (mut position, mut velocity) = (position, velocity);
+                for stage in 0..3 {
+                    position += velocity * (drifts[stage] * dt);
+                    velocity += acceleration(position, velocity) * (kicks[stage] * dt);
+                }
+                (position + velocity * (drifts[3] * dt), velocity)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::math::Vec2;
+
+    // Unit mass and unit spring constant harmonic oscillator.
+    fn energy_drift(integrator: Integrator, steps: usize) -> Real {
+        let spring = |position: Vec2, _| -position;
+        let energy = |position: Vec2, velocity: Vec2| (position.square_length() + velocity.square_length()) / 2.0;
+
+        let (mut position, mut velocity) = (Vec2::x(1.0), Vec2::zero());
+        let initial_energy = energy(position, velocity);
+        let mut max_drift: Real = 0.0;
+        for _ in 0..steps {
+            let state = integrator.step(position, velocity, 0.05, spring);
+            position = state.0;
+            velocity = state.1;
+            max_drift = max_drift.max((energy(position, velocity) - initial_energy).abs());
+        }
+        max_drift / initial_energy
+    }
+
+    #[test]
+    fn symplectic_energy_is_bounded() {
+        assert!(energy_drift(Integrator::VelocityVerlet, 20000) < 1e-3);
+        assert!(energy_drift(Integrator::Yoshida4, 20000) < 1e-4);
+    }
+
+    #[test]
+    fn higher_order_is_more_accurate() {
+        assert!(energy_drift(Integrator::RungeKutta4, 100) < energy_drift(Integrator::VelocityVerlet, 100));
+        assert!(energy_drift(Integrator::VelocityVerlet, 100) < energy_drift(Integrator::SemiImplicitEuler, 100));
+    }
+}
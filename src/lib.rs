@@ -1,12 +1,33 @@
+#[cfg(not(feature = "parallel"))]
 mod util;
+mod random;
 mod spatial_table;
+mod neighbor_list;
+mod parallel;
 
 pub mod math;
 pub mod shapes;
 pub mod body;
+pub mod integrator;
 pub mod space;
+pub mod stepper;
+pub mod events;
+pub mod thermostat;
+pub mod barostat;
+pub mod broad_phase;
+pub mod statistics;
+pub mod reordering;
 
-pub use math::{Vec2, bounding, toroidal};
+pub use math::{Real, Vector, Vec1, Vec2, Vec3, consts, bounding, toroidal, topology, lattice};
 pub use shapes::{Shape, Contact};
 pub use body::{Body};
-pub use space::{Space};
+pub use integrator::{Integrator, ForceField};
+pub use parallel::{Shareable};
+pub use space::{Space, BodyHandle};
+pub use stepper::{FixedStepper};
+pub use events::{SeamCrossing, Axis, Direction};
+pub use thermostat::{Thermostat};
+pub use barostat::{Barostat};
+pub use broad_phase::{BroadPhase};
+pub use statistics::{Statistics};
+pub use reordering::{SpaceFillingCurve};
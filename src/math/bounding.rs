@@ -1,51 +1,81 @@
-use super::Vec2;
+use super::{Vector, Vec2, Real};
 
-pub struct AxisAlignmentBoundingBox {
-    left: f32,
-    right: f32,
-    top: f32,
-    bottom: f32,
+/// Box aligned with the axes of a `D`-dimensional space, 2D by default.
+/// In 2D, the minimum corner is the (left, top) corner and the maximum corner the (right, bottom) one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AxisAlignmentBoundingBox<const D: usize = 2> {
+    min: Vector<D>,
+    max: Vector<D>,
 }
 
-impl AxisAlignmentBoundingBox {
-    pub fn new(position: Vec2, half_size: Vec2) -> AxisAlignmentBoundingBox {
+impl<const D: usize> AxisAlignmentBoundingBox<D> {
+    pub fn new(position: Vector<D>, half_size: Vector<D>) -> AxisAlignmentBoundingBox<D> {
         AxisAlignmentBoundingBox {
-            left: position.x - half_size.x,
-            right: position.x + half_size.x,
-            top: position.y - half_size.y,
-            bottom: position.y + half_size.y,
+            min: position - half_size,
+            max: position + half_size,
         }
     }
 
-    pub fn from_bounds(left: f32, right: f32, top: f32, bottom: f32) -> AxisAlignmentBoundingBox {
-        AxisAlignmentBoundingBox { left, right, top, bottom }
+    pub fn from_corners(min: Vector<D>, max: Vector<D>) -> AxisAlignmentBoundingBox<D> {
+        AxisAlignmentBoundingBox { min, max }
+    }
+
+    pub fn min(&self) -> Vector<D> {
+        self.min
+    }
+
+    pub fn max(&self) -> Vector<D> {
+        self.max
+    }
+
+    pub fn position(&self) -> Vector<D> {
+        (self.max + self.min) / 2.0
+    }
+
+    pub fn half_dimension(&self) -> Vector<D> {
+        (self.max - self.min) / 2.0
     }
 
-    pub fn left(&self) -> f32 {
-        self.left
+    pub fn dimension(&self) -> Vector<D> {
+        self.max - self.min
     }
 
-    pub fn right(&self) -> f32 {
-        self.right
+    /// Whether both boxes share some point. Touching boxes intersect.
+    pub fn intersects(&self, other: &AxisAlignmentBoundingBox<D>) -> bool {
+        (0..D).all(|axis| self.min[axis] <= other.max[axis] && other.min[axis] <= self.max[axis])
     }
 
-    pub fn top(&self) -> f32 {
-        self.top
+    pub fn contains(&self, other: &AxisAlignmentBoundingBox<D>) -> bool {
+        (0..D).all(|axis| self.min[axis] <= other.min[axis] && other.max[axis] <= self.max[axis])
+    }
+
+    /// Smallest box containing both boxes.
+    pub fn union(&self, other: &AxisAlignmentBoundingBox<D>) -> AxisAlignmentBoundingBox<D> {
+        AxisAlignmentBoundingBox {
+            min: Vector::from_fn(|axis| self.min[axis].min(other.min[axis])),
+            max: Vector::from_fn(|axis| self.max[axis].max(other.max[axis])),
+        }
+    }
+}
+
+impl AxisAlignmentBoundingBox {
+    pub fn from_bounds(left: Real, right: Real, top: Real, bottom: Real) -> AxisAlignmentBoundingBox {
+        AxisAlignmentBoundingBox { min: Vec2::xy(left, top), max: Vec2::xy(right, bottom) }
     }
 
-    pub fn bottom(&self) -> f32 {
-        self.bottom
+    pub fn left(&self) -> Real {
+        self.min.x
     }
 
-    pub fn position(&self) -> Vec2 {
-        Vec2::xy((self.right + self.left) / 2.0, (self.bottom + self.top) / 2.0)
+    pub fn right(&self) -> Real {
+        self.max.x
     }
 
-    pub fn half_dimension(&self) -> Vec2 {
-        Vec2::xy((self.right - self.left) / 2.0, (self.bottom - self.top) / 2.0)
+    pub fn top(&self) -> Real {
+        self.min.y
     }
 
-    pub fn dimension(&self) -> Vec2 {
-        Vec2::xy(self.right - self.left, self.bottom - self.top)
+    pub fn bottom(&self) -> Real {
+        self.max.y
     }
 }
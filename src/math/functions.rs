@@ -0,0 +1,249 @@
+//! Transcendental functions of [`Real`] values used by the engine.
+//! By default they are the functions of the standard library, which call the math library
+//! of the platform: their last bits may change between operating systems, compilers or processors.
+//! With the `deterministic` feature they are computed with additions, multiplications, divisions
+//! and square roots only, whose IEEE 754 results are the same on every platform.
+//! Use them in the force fields of a simulation that must be reproducible across machines.
+//!
+//! The feature only covers the code of the engine, which computes its transcendental functions here,
+//! avoids `powi` and sorts the contacts before resolving them.
+//! Force fields calling the methods of `f32`/`f64` directly (`powi`, `exp`, `sin`...)
+//! may still give different results on different platforms.
+
+use super::Real;
+
+#[cfg(not(feature = "deterministic"))]
+pub fn exp(x: Real) -> Real {
+    x.exp()
+}
+
+#[cfg(not(feature = "deterministic"))]
+pub fn ln(x: Real) -> Real {
+    x.ln()
+}
+
+#[cfg(not(feature = "deterministic"))]
+pub fn sin(x: Real) -> Real {
+    x.sin()
+}
+
+#[cfg(not(feature = "deterministic"))]
+pub fn cos(x: Real) -> Real {
+    x.cos()
+}
+
+#[cfg(not(feature = "deterministic"))]
+pub fn cbrt(x: Real) -> Real {
+    x.cbrt()
+}
+
+/// Power of a non-negative base.
+#[cfg(not(feature = "deterministic"))]
+pub fn powf(base: Real, exponent: Real) -> Real {
+    base.powf(exponent)
+}
+
+#[cfg(feature = "deterministic")]
+pub fn exp(x: Real) -> Real {
+    portable::exp(wide(x)) as Real
+}
+
+#[cfg(feature = "deterministic")]
+pub fn ln(x: Real) -> Real {
+    portable::ln(wide(x)) as Real
+}
+
+#[cfg(feature = "deterministic")]
+pub fn sin(x: Real) -> Real {
+    portable::sin(wide(x)) as Real
+}
+
+#[cfg(feature = "deterministic")]
+pub fn cos(x: Real) -> Real {
+    portable::cos(wide(x)) as Real
+}
+
+#[cfg(feature = "deterministic")]
+pub fn cbrt(x: Real) -> Real {
+    portable::cbrt(wide(x)) as Real
+}
+
+/// Power of a non-negative base.
+#[cfg(feature = "deterministic")]
+pub fn powf(base: Real, exponent: Real) -> Real {
+    portable::powf(wide(base), wide(exponent)) as Real
+}
+
+/// The portable functions are evaluated in `f64` whatever the precision of [`Real`].
+#[cfg(feature = "deterministic")]
+#[allow(clippy::unnecessary_cast)]
+fn wide(x: Real) -> f64 {
+    x as f64
+}
+
+/// Logarithm in any base.
+pub fn log(x: Real, base: Real) -> Real {
+    ln(x) / ln(base)
+}
+
+/// Range reductions followed by Taylor series, evaluated in `f64`.
+/// The results are within a few units in the last place of `f64`, enough for `f32` and `f64` values.
+#[cfg(feature = "deterministic")]
+mod portable {
+    use std::f64::consts::{LN_2, FRAC_PI_2, SQRT_2};
+
+    // ln(2) and pi / 2 split in a part with trailing zero bits, exactly multiplied by small integers,
+    // and the rest (Cody-Waite reduction).
+    const LN_2_HIGH: f64 = 6.931_471_803_691_238e-1;
+    const LN_2_LOW: f64 = 1.908_214_929_270_587_7e-10;
+    const FRAC_PI_2_HIGH: f64 = 1.570_796_326_734_125_6;
+    const FRAC_PI_2_LOW: f64 = 6.077_100_506_506_192e-11;
+
+    /// Sum of the coefficients times the powers of x, from the highest power.
+    fn polynomial(x: f64, coefficients: &[f64]) -> f64 {
+        coefficients.iter().rev().fold(0.0, |sum, coefficient| sum * x + coefficient)
+    }
+
+    /// 1 / n! for n from 0 to 17.
+    fn inverse_factorials() -> [f64; 18] {
+        std::array::from_fn(|n| (1..=n).fold(1.0, |factorial, k| factorial / k as f64))
+    }
+
+    /// x times 2^exponent.
+    fn scale(mut x: f64, mut exponent: i32) -> f64 {
+        while exponent != 0 {
+            let step = exponent.clamp(-1000, 1000);
+            x *= f64::from_bits(((step + 1023) as u64) << 52);
+            exponent -= step;
+        }
+        x
+    }
+
+    pub fn exp(x: f64) -> f64 {
+        if x.is_nan() {
+            return x
+        }
+        if x > 709.8 {
+            return f64::INFINITY
+        }
+        if x < -745.2 {
+            return 0.0
+        }
+        let k = (x / LN_2).round();
+        let r = (x - k * LN_2_HIGH) - k * LN_2_LOW; // |r| <= ln(2) / 2
+        scale(polynomial(r, &inverse_factorials()[..15]), k as i32)
+    }
+
+    pub fn ln(x: f64) -> f64 {
+        if x.is_nan() || x < 0.0 {
+            return f64::NAN
+        }
+        if x == 0.0 {
+            return f64::NEG_INFINITY
+        }
+        if x.is_infinite() {
+            return x
+        }
+
+        // x = m * 2^e with m in [sqrt(2) / 2, sqrt(2)).
+        let (x, subnormal_shift) = if x < f64::MIN_POSITIVE { (x * scale(1.0, 54), 54) } else { (x, 0) };
+        let bits = x.to_bits();
+        let mut exponent = ((bits >> 52) & 0x7ff) as i32 - 1023 - subnormal_shift;
+        let mut m = f64::from_bits((bits & ((1 << 52) - 1)) | (1023 << 52));
+        if m > SQRT_2 {
+            m /= 2.0;
+            exponent += 1;
+        }
+
+        // ln(m) = 2 * atanh(s) = 2 * (s + s^3 / 3 + s^5 / 5 + ...), with |s| < 0.18.
+        let s = (m - 1.0) / (m + 1.0);
+        let coefficients: [f64; 12] = std::array::from_fn(|n| 1.0 / (2 * n + 1) as f64);
+        let series = polynomial(s * s, &coefficients);
+        exponent as f64 * LN_2_HIGH + (exponent as f64 * LN_2_LOW + 2.0 * s * series)
+    }
+
+    /// Sine and cosine of the reduced angle, |r| <= pi / 4.
+    fn reduced_sin_cos(r: f64) -> (f64, f64) {
+        let factorials = inverse_factorials();
+        let signed = |power: usize| if power % 4 < 2 { factorials[power] } else { -factorials[power] };
+        let sin_coefficients: [f64; 9] = std::array::from_fn(|n| signed(2 * n + 1));
+        let cos_coefficients: [f64; 9] = std::array::from_fn(|n| signed(2 * n));
+        (r * polynomial(r * r, &sin_coefficients), polynomial(r * r, &cos_coefficients))
+    }
+
+    /// Sine and cosine, reducing the angle by multiples of pi / 2.
+    fn sin_cos(x: f64) -> (f64, f64) {
+        if !x.is_finite() {
+            return (f64::NAN, f64::NAN)
+        }
+        let k = (x / FRAC_PI_2).round();
+        let (sin, cos) = reduced_sin_cos((x - k * FRAC_PI_2_HIGH) - k * FRAC_PI_2_LOW);
+        match k.rem_euclid(4.0) as u8 {
+            0 => (sin, cos),
+            1 => (cos, -sin),
+            2 => (-sin, -cos),
+            _ => (-cos, sin),
+        }
+    }
+
+    pub fn sin(x: f64) -> f64 {
+        sin_cos(x).0
+    }
+
+    pub fn cos(x: f64) -> f64 {
+        sin_cos(x).1
+    }
+
+    pub fn cbrt(x: f64) -> f64 {
+        if x == 0.0 || !x.is_finite() {
+            return x
+        }
+        let y = exp(ln(x.abs()) / 3.0);
+        let y = y - (y * y * y - x.abs()) / (3.0 * y * y); // Newton step
+        if x < 0.0 { -y } else { y }
+    }
+
+    pub fn powf(base: f64, exponent: f64) -> f64 {
+        if exponent == 0.0 {
+            return 1.0
+        }
+        if base == 0.0 {
+            return if exponent > 0.0 { 0.0 } else { f64::INFINITY }
+        }
+        exp(exponent * ln(base))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn assert_close(value: f64, expected: f64) {
+            let error = (value - expected).abs() / expected.abs().max(1.0);
+            assert!(error < 1e-14, "{} instead of {}", value, expected);
+        }
+
+        #[test]
+        fn same_values_as_the_standard_library() {
+            for i in -200..200 {
+                let x = i as f64 * 0.173;
+                assert_close(exp(x), x.exp());
+                assert_close(sin(x), x.sin());
+                assert_close(cos(x), x.cos());
+                assert_close(cbrt(x), x.cbrt());
+                let positive = (i + 201) as f64 * 0.0371;
+                assert_close(ln(positive), positive.ln());
+                assert_close(powf(positive, x / 10.0), positive.powf(x / 10.0));
+            }
+            assert_close(ln(1e-310), 1e-310_f64.ln());
+            assert_eq!(exp(-1000.0), 0.0);
+            assert_eq!(exp(1000.0), f64::INFINITY);
+            assert!(ln(-1.0).is_nan());
+        }
+
+        #[test]
+        fn split_constants_add_up() {
+            assert_eq!(LN_2_HIGH + LN_2_LOW, LN_2);
+            assert_eq!(FRAC_PI_2_HIGH + FRAC_PI_2_LOW, FRAC_PI_2);
+        }
+    }
+}
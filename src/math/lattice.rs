@@ -0,0 +1,140 @@
+use super::{Vector, Vec2, Real};
+
+/// Periodic lattice of `D` dimensions generated by `D` linearly independent vectors.
+/// The lattice vectors are the edges of the unit cell: a parallelogram in 2D
+/// (triclinic or hexagonal cells) and a parallelepiped in 3D.
+/// Positions are expressed in fractional coordinates as the combination of the lattice vectors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lattice<const D: usize> {
+    vectors: [Vector<D>; D],
+    reciprocal: [Vector<D>; D], // rows of the inverse of the matrix whose columns are the vectors
+}
+
+impl Lattice<2> {
+    /// Hexagonal lattice of the given side: two vectors of the same length at 60 degrees.
+    pub fn hexagonal(side: Real) -> Lattice<2> {
+        Lattice::new([Vec2::x(side), Vec2::xy(side / 2.0, side * (3.0 as Real).sqrt() / 2.0)])
+    }
+}
+
+impl<const D: usize> Lattice<D> {
+    /// Creates a lattice from its vectors.
+    /// Panics if the vectors are not linearly independent.
+    pub fn new(vectors: [Vector<D>; D]) -> Lattice<D> {
+        Lattice { vectors, reciprocal: inverse_rows(&vectors) }
+    }
+
+    pub fn vectors(&self) -> &[Vector<D>; D] {
+        &self.vectors
+    }
+
+    /// Coordinates of a position in the basis of the lattice vectors.
+    /// Positions inside the unit cell have all the fractional coordinates in [0, 1).
+    pub fn to_fractional(&self, position: Vector<D>) -> Vector<D> {
+        Vector::from_fn(|axis| self.reciprocal[axis] * position)
+    }
+
+    pub fn to_cartesian(&self, fractional: Vector<D>) -> Vector<D> {
+        (0..D).fold(Vector::zero(), |position, axis| position + self.vectors[axis] * fractional[axis])
+    }
+
+    /// Distance between each pair of opposite faces of the unit cell.
+    /// A displacement of length `l` never changes the fractional coordinate `i` more than `l / widths[i]`.
+    pub fn widths(&self) -> Vector<D> {
+        Vector::from_fn(|axis| 1.0 / self.reciprocal[axis].length())
+    }
+
+    /// Maximum change of each fractional coordinate inside a box of the given half dimension.
+    pub fn fractional_half_extent(&self, half_dimension: Vector<D>) -> Vector<D> {
+        Vector::from_fn(|axis| self.reciprocal[axis].map(|_, value| value.abs()) * half_dimension)
+    }
+
+    /// Length, area or volume of the unit cell.
+    pub fn volume(&self) -> Real {
+        1.0 / determinant(&self.reciprocal).abs()
+    }
+
+    /// Lattice whose vectors are the vectors of this one multiplied by each factor.
+    /// The width of each axis is multiplied by the same factor.
+    pub fn scaled(&self, factors: Vector<D>) -> Lattice<D> {
+        let mut vectors = self.vectors;
+        for (axis, vector) in vectors.iter_mut().enumerate() {
+            *vector *= factors[axis];
+        }
+        Lattice::new(vectors)
+    }
+
+    /// Whether the lattice vectors are perpendicular to each other.
+    pub fn is_orthogonal(&self) -> bool {
+        (0..D).all(|i| (i + 1..D).all(|j| (self.vectors[i] * self.vectors[j]).abs() <= Real::EPSILON))
+    }
+}
+
+/// Inverse of the matrix whose columns are the vectors, by Gauss-Jordan elimination.
+fn inverse_rows<const D: usize>(vectors: &[Vector<D>; D]) -> [Vector<D>; D] {
+    let mut matrix = [Vector::<D>::zero(); D];
+    let mut inverse = [Vector::<D>::zero(); D];
+    for row in 0..D {
+        matrix[row] = Vector::from_fn(|column| vectors[column][row]);
+        inverse[row] = Vector::axis(row, 1.0);
+    }
+
+    for column in 0..D {
+        let pivot = (column..D)
+            .max_by(|&a, &b| matrix[a][column].abs().partial_cmp(&matrix[b][column].abs()).unwrap())
+            .unwrap();
+        assert!(matrix[pivot][column].abs() > Real::EPSILON, "The lattice vectors must be linearly independent");
+        matrix.swap(column, pivot);
+        inverse.swap(column, pivot);
+
+        let scale = 1.0 / matrix[column][column];
+        matrix[column] *= scale;
+        inverse[column] *= scale;
+        for row in 0..D {
+            if row != column {
+                let factor = matrix[row][column];
+                let (matrix_row, inverse_row) = (matrix[column] * factor, inverse[column] * factor);
+                matrix[row] -= matrix_row;
+                inverse[row] -= inverse_row;
+            }
+        }
+    }
+    inverse
+}
+
+fn determinant<const D: usize>(rows: &[Vector<D>; D]) -> Real {
+    let mut matrix = *rows;
+    let mut determinant = 1.0;
+    for column in 0..D {
+        let pivot = match (column..D).find(|&row| matrix[row][column] != 0.0) {
+            Some(pivot) => pivot,
+            None => return 0.0,
+        };
+        if pivot != column {
+            matrix.swap(column, pivot);
+            determinant = -determinant;
+        }
+        determinant *= matrix[column][column];
+        for row in column + 1..D {
+            let factor = matrix[row][column] / matrix[column][column];
+            let pivot_row = matrix[column] * factor;
+            matrix[row] -= pivot_row;
+        }
+    }
+    determinant
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fractional_coordinates() {
+        let lattice = Lattice::new([Vec2::x(10.0), Vec2::xy(5.0, 10.0)]);
+        assert_eq!(lattice.to_fractional(Vec2::xy(10.0, 10.0)), Vec2::xy(0.5, 1.0));
+        assert_eq!(lattice.to_cartesian(Vec2::xy(0.5, 1.0)), Vec2::xy(10.0, 10.0));
+        assert!((lattice.widths() - Vec2::xy(4.0 * (5.0 as Real).sqrt(), 10.0)).length() < 1e-3);
+        assert!((lattice.volume() - 100.0).abs() < 1e-3);
+        assert!(!lattice.is_orthogonal());
+    }
+}
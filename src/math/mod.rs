@@ -1,5 +1,24 @@
-pub mod vec2;
+pub mod vector;
 pub mod toroidal;
 pub mod bounding;
+pub mod topology;
+pub mod lattice;
+pub mod functions;
 
-pub use vec2::Vec2;
+pub use vector::{Vector, Vec1, Vec2, Vec3};
+
+/// Floating point type used by the whole engine: `f32` by default or `f64` with the `f64` feature.
+#[cfg(not(feature = "f64"))]
+pub type Real = f32;
+
+/// Floating point type used by the whole engine: `f32` by default or `f64` with the `f64` feature.
+#[cfg(feature = "f64")]
+pub type Real = f64;
+
+/// Mathematical constants of the [`Real`] type.
+#[cfg(not(feature = "f64"))]
+pub use std::f32::consts;
+
+/// Mathematical constants of the [`Real`] type.
+#[cfg(feature = "f64")]
+pub use std::f64::consts;
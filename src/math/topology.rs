@@ -0,0 +1,652 @@
+use super::{Vector, Vec2, Real};
+use super::bounding::AxisAlignmentBoundingBox;
+use super::toroidal::{Bounds, min_coordinate, min_distance};
+use crate::parallel::Shareable;
+
+/// Mirror transformation of the directions (velocities, normals, forces)
+/// produced when a path crosses a seam glued with a flip.
+/// Each axis whose component is mirrored is marked in `flips`.
+/// Mirror transformations are their own inverse.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Orientation<const D: usize = 2> {
+    pub flips: [bool; D],
+}
+
+impl Orientation {
+    pub fn new(flip_x: bool, flip_y: bool) -> Orientation {
+        Orientation { flips: [flip_x, flip_y] }
+    }
+
+    pub fn flip_x(&self) -> bool {
+        self.flips[0]
+    }
+
+    pub fn flip_y(&self) -> bool {
+        self.flips[1]
+    }
+}
+
+impl<const D: usize> Orientation<D> {
+    pub fn identity() -> Orientation<D> {
+        Orientation { flips: [false; D] }
+    }
+
+    pub fn from_flips(flips: [bool; D]) -> Orientation<D> {
+        Orientation { flips }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.flips.iter().all(|flip| !flip)
+    }
+
+    /// Composition of two orientations.
+    pub fn then(&self, other: Orientation<D>) -> Orientation<D> {
+        let mut flips = self.flips;
+        for (flip, other_flip) in flips.iter_mut().zip(other.flips.iter()) {
+            *flip = *flip != *other_flip;
+        }
+        Orientation { flips }
+    }
+
+    pub fn apply(&self, direction: Vector<D>) -> Vector<D> {
+        direction.map(|axis, component| if self.flips[axis] { -component } else { component })
+    }
+}
+
+impl<const D: usize> Default for Orientation<D> {
+    fn default() -> Orientation<D> {
+        Orientation::identity()
+    }
+}
+
+/// Result of applying the edges of a space to a position that could be outside of it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Confinement<const D: usize = 2> {
+    /// The position is inside the space.
+    /// The orientation must be applied to the directions, as in [`Topology::normalize()`].
+    /// The reflection marks the axes whose velocity component bounced against a wall.
+    /// The velocity shift must be added to the velocity after applying the orientation (sheared boundaries).
+    /// The winding is the number of times the position turned around each pair of glued edges:
+    /// positive when leaving by the right (or bottom) edge and negative when leaving by the left (or top) edge.
+    Inside {
+        position: Vector<D>,
+        orientation: Orientation<D>,
+        reflection: Orientation<D>,
+        velocity_shift: Vector<D>,
+        winding: [i32; D],
+    },
+
+    /// The position left the space through an open edge.
+    Absorbed,
+}
+
+/// Periodic image of a position seen from another position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Image<const D: usize = 2> {
+    /// Vector from the image to the position it is seen from.
+    pub displacement: Vector<D>,
+
+    /// Maps directions at the original position into directions at the image.
+    pub orientation: Orientation<D>,
+
+    /// Velocity of the image relative to the original position, once the orientation is applied.
+    pub velocity_shift: Vector<D>,
+}
+
+/// A space of `D` dimensions (2D by default) built from the box [0, width) x [0, height) x ...
+/// gluing its edges. The way the edges are glued defines the topology of the space.
+/// With the `parallel` feature, the topology is read from several threads and must be `Send + Sync`.
+pub trait Topology<const D: usize = 2>: Shareable {
+    fn dimension(&self) -> Vector<D>;
+
+    /// Changes the dimension of the fundamental box.
+    fn resize(&mut self, dimension: Vector<D>);
+
+    /// Maps a position into the fundamental box.
+    /// The returned orientation must be applied to the directions (i.e. the velocity)
+    /// of a body moved from the original position to the normalized one.
+    fn normalize(&self, position: Vector<D>) -> (Vector<D>, Orientation<D>);
+
+    /// Similar to [`Topology::normalize()`] but for spaces whose edges are not all glued.
+    /// The half extent is the half dimension of the shape of the body at the position:
+    /// a body bounces against a wall when its shape reaches it.
+    /// By default, all the edges are glued and the position is normalized.
+    fn confine(&self, position: Vector<D>, _half_extent: Vector<D>) -> Confinement<D> {
+        let dimension = self.dimension();
+        let mut winding = [0; D];
+        for (axis, turns) in winding.iter_mut().enumerate() {
+            *turns = (position[axis] / dimension[axis]).floor() as i32;
+        }
+        let (position, orientation) = self.normalize(position);
+        Confinement::Inside {
+            position,
+            orientation,
+            reflection: Orientation::identity(),
+            velocity_shift: Vector::zero(),
+            winding,
+        }
+    }
+
+    /// Length, area or volume of the space.
+    fn cell_volume(&self) -> Real {
+        self.dimension().product()
+    }
+
+    /// Translation between a position and its image after the given number of turns around each pair of
+    /// glued edges (see the winding of [`Confinement::Inside`]), ignoring the orientation of the image.
+    fn translation(&self, winding: [i32; D]) -> Vector<D> {
+        self.dimension().map(|axis, length| winding[axis] as Real * length)
+    }
+
+    /// Velocity of the nearest image of `to` relative to `to` itself, once the orientation is applied.
+    /// It is zero except for spaces whose images move, as the sheared [`Bounds`].
+    fn image_velocity(&self, _from: Vector<D>, _to: Vector<D>) -> Vector<D> {
+        Vector::zero()
+    }
+
+    /// Advances the time dependent state of the topology, if any.
+    fn advance(&mut self, _dt: Real) {}
+
+    /// Minimum image displacement: the shortest vector from the nearest image of `to` to `from`.
+    /// The positions can be outside the fundamental box, as the positions of the bodies
+    /// between their integration and their confinement.
+    /// The returned displacement is expressed in the directions at `from`,
+    /// and the returned orientation maps directions at `to` into directions at its nearest image.
+    fn displacement(&self, from: Vector<D>, to: Vector<D>) -> (Vector<D>, Orientation<D>);
+
+    /// Pushes every image of `to` closer than `distance` to `from`.
+    /// In small spaces, or for large distances, there can be several images of the same position.
+    /// By default, only the nearest image (see [`Topology::displacement()`]) is considered.
+    fn images(&self, from: Vector<D>, to: Vector<D>, distance: Real, output: &mut Vec<Image<D>>) {
+        let (displacement, orientation) = self.displacement(from, to);
+        if displacement.square_length() < distance * distance {
+            output.push(Image { displacement, orientation, velocity_shift: self.image_velocity(from, to) });
+        }
+    }
+
+    /// Splits an AABB into the regions of the fundamental box that it covers.
+    /// For skewed [`Bounds`], the regions are expressed in its grid coordinates.
+    /// The pushed AABBs follow the [`Bounds::get_toroidal_aabb()`] convention:
+    /// a minimum coordinate greater than the maximum one (i.e. a left greater than the right)
+    /// wraps around the edge.
+    fn wrapped_aabbs(&self, aabb: &AxisAlignmentBoundingBox<D>, output: &mut Vec<AxisAlignmentBoundingBox<D>>);
+}
+
+impl<const D: usize> Topology<D> for Bounds<D> {
+    fn dimension(&self) -> Vector<D> {
+        Bounds::dimension(self)
+    }
+
+    fn resize(&mut self, dimension: Vector<D>) {
+        Bounds::resize(self, dimension);
+    }
+
+    fn cell_volume(&self) -> Real {
+        Bounds::cell_volume(self)
+    }
+
+    fn translation(&self, winding: [i32; D]) -> Vector<D> {
+        self.get_image_translation(winding)
+    }
+
+    fn normalize(&self, position: Vector<D>) -> (Vector<D>, Orientation<D>) {
+        (self.get_toroidal_position(position), Orientation::identity())
+    }
+
+    fn confine(&self, position: Vector<D>, half_extent: Vector<D>) -> Confinement<D> {
+        Bounds::confine(self, position, half_extent)
+    }
+
+    fn image_velocity(&self, from: Vector<D>, to: Vector<D>) -> Vector<D> {
+        self.get_image_velocity(from - to)
+    }
+
+    fn advance(&mut self, dt: Real) {
+        self.advance_shear(dt);
+    }
+
+    fn images(&self, from: Vector<D>, to: Vector<D>, distance: Real, output: &mut Vec<Image<D>>) {
+        if self.lattice().is_none() && (0..D).all(|axis| distance * 2.0 <= self.lengths[axis]) {
+            // Closer than half the lengths, only the nearest image can touch.
+            let displacement = self.get_toroidal_distance(from - to);
+            push_image(output, displacement, Orientation::identity(), self.get_image_velocity(from - to), distance);
+            return
+        }
+        let point = AxisAlignmentBoundingBox::new(to, Vector::zero());
+        let neighborhood = AxisAlignmentBoundingBox::new(from, Vector::splat(distance));
+        self.for_each_image(&point, &neighborhood, |translation| {
+            let displacement = from - (to + translation);
+            if displacement.square_length() < distance * distance {
+                output.push(Image {
+                    displacement,
+                    orientation: Orientation::identity(),
+                    // The translation spans the same number of heights as the distance to the image.
+                    velocity_shift: self.get_image_velocity(translation),
+                });
+            }
+        });
+    }
+
+    fn displacement(&self, from: Vector<D>, to: Vector<D>) -> (Vector<D>, Orientation<D>) {
+        (self.get_toroidal_distance(from - to), Orientation::identity())
+    }
+
+    fn wrapped_aabbs(&self, aabb: &AxisAlignmentBoundingBox<D>, output: &mut Vec<AxisAlignmentBoundingBox<D>>) {
+        let offset = self.shear_offset();
+        let inside = (0..D).all(|axis| aabb.min()[axis] >= 0.0 && aabb.max()[axis] < self.lengths[axis]);
+        if offset == 0.0 && self.lattice().is_none() && inside {
+            // Most AABBs are far from the edges and stay as they are.
+            output.push(*aabb);
+            return
+        }
+        if offset == 0.0 {
+            output.push(self.get_toroidal_aabb(&self.get_grid_aabb(aabb)));
+            return
+        }
+
+        // The parts across the horizontal edges are shifted by the shear offset.
+        split_seam(aabb.min()[1], aabb.max()[1], self.lengths[1], |turns, top, bottom| {
+            let shift = Vector::axis(0, turns as Real * offset);
+            let (mut min, mut max) = (aabb.min() - shift, aabb.max() - shift);
+            min[1] = top;
+            max[1] = bottom;
+            output.push(self.get_toroidal_aabb(&AxisAlignmentBoundingBox::from_corners(min, max)));
+        });
+    }
+}
+
+/// Torus whose vertical edges are glued with a vertical offset:
+/// the point (x + width, y + twist) is the point (x, y).
+pub struct TwistedTorus {
+    pub width: Real,
+    pub height: Real,
+    pub twist: Real,
+}
+
+impl TwistedTorus {
+    pub fn new(width: Real, height: Real, twist: Real) -> TwistedTorus {
+        TwistedTorus { width, height, twist }
+    }
+}
+
+impl Topology for TwistedTorus {
+    fn dimension(&self) -> Vec2 {
+        Vec2::xy(self.width, self.height)
+    }
+
+    fn resize(&mut self, dimension: Vec2) {
+        self.twist *= dimension.y / self.height;
+        self.width = dimension.x;
+        self.height = dimension.y;
+    }
+
+    fn normalize(&self, position: Vec2) -> (Vec2, Orientation) {
+        let turns = (position.x / self.width).floor();
+        let position = Vec2::xy(
+            min_coordinate(position.x, self.width),
+            min_coordinate(position.y - turns * self.twist, self.height),
+        );
+        (position, Orientation::identity())
+    }
+
+    fn displacement(&self, from: Vec2, to: Vec2) -> (Vec2, Orientation) {
+        let distance = from - to;
+        let candidates = (-1..=1).map(|turns: i32| {
+            let turns = turns as Real;
+            let displacement = Vec2::xy(
+                distance.x - turns * self.width,
+                min_distance(distance.y - turns * self.twist, self.height),
+            );
+            (displacement, Orientation::identity())
+        });
+        nearest(candidates)
+    }
+
+    fn images(&self, from: Vec2, to: Vec2, distance: Real, output: &mut Vec<Image>) {
+        let offset = from - to;
+        for_each_turn(offset.x, self.width, distance, |x_turns| {
+            let offset_y = offset.y - x_turns as Real * self.twist;
+            for_each_turn(offset_y, self.height, distance, |y_turns| {
+                let displacement = Vec2::xy(offset.x - x_turns as Real * self.width, offset_y - y_turns as Real * self.height);
+                push_image(output, displacement, Orientation::identity(), Vec2::zero(), distance);
+            });
+        });
+    }
+
+    fn wrapped_aabbs(&self, aabb: &AxisAlignmentBoundingBox, output: &mut Vec<AxisAlignmentBoundingBox>) {
+        split_vertical_seam(aabb, self.width, |turns, left, right| {
+            let shift = turns as Real * self.twist;
+            let (top, bottom) = wrapped_range(aabb.top() - shift, aabb.bottom() - shift, self.height);
+            output.push(AxisAlignmentBoundingBox::from_bounds(left, right, top, bottom));
+        });
+    }
+}
+
+/// Klein bottle: the horizontal edges are glued as in a torus
+/// and the vertical edges are glued with a vertical flip:
+/// the point (x + width, height - y) is the point (x, y).
+/// Crossing the vertical edges mirrors the vertical component of the velocity.
+pub struct KleinBottle {
+    pub width: Real,
+    pub height: Real,
+}
+
+impl KleinBottle {
+    pub fn new(width: Real, height: Real) -> KleinBottle {
+        KleinBottle { width, height }
+    }
+}
+
+impl Topology for KleinBottle {
+    fn dimension(&self) -> Vec2 {
+        Vec2::xy(self.width, self.height)
+    }
+
+    fn resize(&mut self, dimension: Vec2) {
+        self.width = dimension.x;
+        self.height = dimension.y;
+    }
+
+    fn normalize(&self, position: Vec2) -> (Vec2, Orientation) {
+        let flip = is_odd((position.x / self.width).floor() as i32);
+        let y = if flip { self.height - position.y } else { position.y };
+        let position = Vec2::xy(min_coordinate(position.x, self.width), min_coordinate(y, self.height));
+        (position, Orientation::new(false, flip))
+    }
+
+    fn displacement(&self, from: Vec2, to: Vec2) -> (Vec2, Orientation) {
+        let frame = BoxFrame::new(self, from, to);
+        let (from, to) = (frame.from, frame.to);
+        let candidates = (-1..=1).map(|turns: i32| {
+            let flip = turns != 0;
+            let image_y = if flip { self.height - to.y } else { to.y };
+            let displacement = Vec2::xy(
+                from.x - to.x - turns as Real * self.width,
+                min_distance(from.y - image_y, self.height),
+            );
+            (displacement, Orientation::new(false, flip))
+        });
+        let (displacement, orientation) = nearest(candidates);
+        frame.restore(displacement, orientation)
+    }
+
+    fn images(&self, from: Vec2, to: Vec2, distance: Real, output: &mut Vec<Image>) {
+        let frame = BoxFrame::new(self, from, to);
+        let (from, to) = (frame.from, frame.to);
+        for_each_turn(from.x - to.x, self.width, distance, |x_turns| {
+            let flip = is_odd(x_turns);
+            let image_y = if flip { self.height - to.y } else { to.y };
+            for_each_turn(from.y - image_y, self.height, distance, |y_turns| {
+                let displacement = Vec2::xy(
+                    from.x - to.x - x_turns as Real * self.width,
+                    from.y - image_y - y_turns as Real * self.height,
+                );
+                let (displacement, orientation) = frame.restore(displacement, Orientation::new(false, flip));
+                push_image(output, displacement, orientation, Vec2::zero(), distance);
+            });
+        });
+    }
+
+    fn wrapped_aabbs(&self, aabb: &AxisAlignmentBoundingBox, output: &mut Vec<AxisAlignmentBoundingBox>) {
+        let height = self.height;
+        split_vertical_seam(aabb, self.width, |turns, left, right| {
+            let (top, bottom) = if is_odd(turns) {
+                (height - aabb.bottom(), height - aabb.top())
+            }
+            else {
+                (aabb.top(), aabb.bottom())
+            };
+            let (top, bottom) = wrapped_range(top, bottom, height);
+            output.push(AxisAlignmentBoundingBox::from_bounds(left, right, top, bottom));
+        });
+    }
+}
+
+/// Real projective plane: both pairs of edges are glued with a flip:
+/// the point (x + width, height - y) and the point (width - x, y + height) are the point (x, y).
+/// Crossing an edge mirrors the velocity component parallel to that edge.
+/// The two corners of the rectangle are singular points of this model:
+/// near them, the minimum image displacement only considers the images across the adjacent edges.
+pub struct ProjectivePlane {
+    pub width: Real,
+    pub height: Real,
+}
+
+impl ProjectivePlane {
+    pub fn new(width: Real, height: Real) -> ProjectivePlane {
+        ProjectivePlane { width, height }
+    }
+}
+
+impl ProjectivePlane {
+    /// Displacements from `to` and from its images across each edge to `from`.
+    fn candidates(&self, from: Vec2, to: Vec2) -> impl Iterator<Item = (Vec2, Orientation)> {
+        let (width, height) = (self.width, self.height);
+        [(0, 0), (-1, 0), (1, 0), (0, -1), (0, 1)].iter().map(move |&(x_turns, y_turns): &(i32, i32)| {
+            let orientation = Orientation::new(y_turns != 0, x_turns != 0);
+            let image_x = if orientation.flip_x() { width - to.x } else { to.x } + x_turns as Real * width;
+            let image_y = if orientation.flip_y() { height - to.y } else { to.y } + y_turns as Real * height;
+            (Vec2::xy(from.x - image_x, from.y - image_y), orientation)
+        })
+    }
+}
+
+impl Topology for ProjectivePlane {
+    fn dimension(&self) -> Vec2 {
+        Vec2::xy(self.width, self.height)
+    }
+
+    fn resize(&mut self, dimension: Vec2) {
+        self.width = dimension.x;
+        self.height = dimension.y;
+    }
+
+    fn normalize(&self, position: Vec2) -> (Vec2, Orientation) {
+        let flip_y = is_odd((position.x / self.width).floor() as i32);
+        let x = min_coordinate(position.x, self.width);
+        let y = if flip_y { self.height - position.y } else { position.y };
+
+        let flip_x = is_odd((y / self.height).floor() as i32);
+        let x = if flip_x { min_coordinate(self.width - x, self.width) } else { x };
+        let y = min_coordinate(y, self.height);
+
+        (Vec2::xy(x, y), Orientation::new(flip_x, flip_y))
+    }
+
+    fn displacement(&self, from: Vec2, to: Vec2) -> (Vec2, Orientation) {
+        let frame = BoxFrame::new(self, from, to);
+        let (displacement, orientation) = nearest(self.candidates(frame.from, frame.to));
+        frame.restore(displacement, orientation)
+    }
+
+    fn images(&self, from: Vec2, to: Vec2, distance: Real, output: &mut Vec<Image>) {
+        let frame = BoxFrame::new(self, from, to);
+        for (displacement, orientation) in self.candidates(frame.from, frame.to) {
+            let (displacement, orientation) = frame.restore(displacement, orientation);
+            push_image(output, displacement, orientation, Vec2::zero(), distance);
+        }
+    }
+
+    fn wrapped_aabbs(&self, aabb: &AxisAlignmentBoundingBox, output: &mut Vec<AxisAlignmentBoundingBox>) {
+        let (width, height) = (self.width, self.height);
+        split_vertical_seam(aabb, self.width, |x_turns, left, right| {
+            let (top, bottom) = if is_odd(x_turns) {
+                (height - aabb.bottom(), height - aabb.top())
+            }
+            else {
+                (aabb.top(), aabb.bottom())
+            };
+            let column = AxisAlignmentBoundingBox::from_bounds(left, right, top, bottom);
+            split_horizontal_seam(&column, self.height, |y_turns, top, bottom| {
+                let (left, right) = if is_odd(y_turns) { (width - right, width - left) } else { (left, right) };
+                output.push(AxisAlignmentBoundingBox::from_bounds(left, right, top, bottom));
+            });
+        });
+    }
+}
+
+/// Two positions moved into the fundamental box, for the topologies whose images are only
+/// listed from positions inside it. Normalizing a position can flip the directions at it.
+struct BoxFrame {
+    from: Vec2,
+    to: Vec2,
+    from_orientation: Orientation,
+    to_orientation: Orientation,
+}
+
+impl BoxFrame {
+    fn new<T: Topology>(topology: &T, from: Vec2, to: Vec2) -> BoxFrame {
+        let (from, from_orientation) = topology.normalize(from);
+        let (to, to_orientation) = topology.normalize(to);
+        BoxFrame { from, to, from_orientation, to_orientation }
+    }
+
+    /// Brings a displacement and an orientation between the positions in the box
+    /// back to the directions at the original positions.
+    fn restore(&self, displacement: Vec2, orientation: Orientation) -> (Vec2, Orientation) {
+        let orientation = self.to_orientation.then(orientation).then(self.from_orientation);
+        (self.from_orientation.apply(displacement), orientation)
+    }
+}
+
+/// Calls the function with every number of turns such that `|offset - turns * length| < reach`.
+fn for_each_turn<F>(offset: Real, length: Real, reach: Real, mut function: F)
+where F: FnMut(i32) {
+    let first = ((offset - reach) / length).ceil() as i32;
+    let last = ((offset + reach) / length).floor() as i32;
+    for turns in first..=last {
+        function(turns);
+    }
+}
+
+fn push_image<const D: usize>(output: &mut Vec<Image<D>>, displacement: Vector<D>, orientation: Orientation<D>, velocity_shift: Vector<D>, distance: Real) {
+    if displacement.square_length() < distance * distance {
+        output.push(Image { displacement, orientation, velocity_shift });
+    }
+}
+
+fn is_odd(turns: i32) -> bool {
+    turns % 2 != 0
+}
+
+fn nearest<I>(candidates: I) -> (Vec2, Orientation)
+where I: Iterator<Item = (Vec2, Orientation)> {
+    candidates
+        .fold(None, |best: Option<(Vec2, Orientation)>, candidate| match best {
+            Some(best) if best.0.square_length() <= candidate.0.square_length() => Some(best),
+            _ => Some(candidate),
+        })
+        .unwrap()
+}
+
+/// Range wrapped into [0, length], following the [`Bounds::get_toroidal_aabb()`] convention:
+/// a range as long as the length covers it whole.
+fn wrapped_range(start: Real, end: Real, length: Real) -> (Real, Real) {
+    if end - start >= length {
+        return (0.0, length)
+    }
+    (min_coordinate(start, length), min_coordinate(end, length))
+}
+
+/// Calls `piece` with the number of turns around the vertical edges and the horizontal range,
+/// already inside [0, length], of each part of the AABB.
+fn split_vertical_seam<F>(aabb: &AxisAlignmentBoundingBox, length: Real, piece: F)
+where F: FnMut(i32, Real, Real) {
+    split_seam(aabb.left(), aabb.right(), length, piece)
+}
+
+/// Same as [`split_vertical_seam()`] for the vertical range and the horizontal edges.
+fn split_horizontal_seam<F>(aabb: &AxisAlignmentBoundingBox, length: Real, piece: F)
+where F: FnMut(i32, Real, Real) {
+    split_seam(aabb.top(), aabb.bottom(), length, piece)
+}
+
+fn split_seam<F>(start: Real, end: Real, length: Real, mut piece: F)
+where F: FnMut(i32, Real, Real) {
+    let first = (start / length).floor() as i32;
+    let last = (end / length).floor() as i32;
+    for turns in first..=last {
+        let offset = turns as Real * length;
+        let piece_start = start.max(offset) - offset;
+        let piece_end = end.min(offset + length) - offset;
+        if piece_end > piece_start || first == last {
+            piece(turns, piece_start, piece_end);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn klein_bottle_flips_across_vertical_edge() {
+        let klein = KleinBottle::new(100.0, 50.0);
+        let (position, orientation) = klein.normalize(Vec2::xy(105.0, 10.0));
+        assert_eq!(position, Vec2::xy(5.0, 40.0));
+        assert_eq!(orientation, Orientation::new(false, true));
+
+        let (displacement, orientation) = klein.displacement(Vec2::xy(2.0, 10.0), Vec2::xy(98.0, 40.0));
+        assert_eq!(displacement, Vec2::xy(4.0, 0.0));
+        assert!(orientation.flip_y());
+    }
+
+    #[test]
+    fn displacements_between_positions_outside_the_box() {
+        let klein = KleinBottle::new(100.0, 50.0);
+        let plane = ProjectivePlane::new(100.0, 50.0);
+
+        // A body that just left the box (not confined yet) keeps the directions it had before crossing the edge.
+        let x_edge = Orientation::new(false, true);
+        assert_eq!(klein.displacement(Vec2::xy(2.0, 10.0), Vec2::xy(101.0, 40.0)), (Vec2::xy(1.0, 0.0), x_edge));
+        assert_eq!(klein.displacement(Vec2::xy(101.0, 40.0), Vec2::xy(2.0, 10.0)), (Vec2::xy(-1.0, 0.0), x_edge));
+
+        // Two turns around the space give the same point with the same directions.
+        let identity = Orientation::identity();
+        assert_eq!(klein.displacement(Vec2::xy(2.0, 10.0), Vec2::xy(-199.0, 10.0)), (Vec2::xy(1.0, 0.0), identity));
+        assert_eq!(plane.displacement(Vec2::xy(20.0, 1.0), Vec2::xy(21.0, -99.0)), (Vec2::xy(-1.0, 0.0), identity));
+
+        let mut images = Vec::new();
+        plane.images(Vec2::xy(20.0, 1.0), Vec2::xy(21.0, -99.0), 5.0, &mut images);
+        assert_eq!(images.len(), 1);
+        assert_eq!((images[0].displacement, images[0].orientation), (Vec2::xy(-1.0, 0.0), identity));
+    }
+
+    #[test]
+    fn twisted_torus_shifts_across_vertical_edge() {
+        let twisted = TwistedTorus::new(100.0, 50.0, 20.0);
+        assert_eq!(twisted.normalize(Vec2::xy(105.0, 30.0)).0, Vec2::xy(5.0, 10.0));
+        assert_eq!(twisted.displacement(Vec2::xy(2.0, 10.0), Vec2::xy(98.0, 30.0)).0, Vec2::xy(4.0, 0.0));
+    }
+
+    #[test]
+    fn projective_plane_flips_both_edges() {
+        let plane = ProjectivePlane::new(100.0, 50.0);
+        let (position, orientation) = plane.normalize(Vec2::xy(20.0, 53.0));
+        assert_eq!(position, Vec2::xy(80.0, 3.0));
+        assert_eq!(orientation, Orientation::new(true, false));
+
+        let (displacement, orientation) = plane.displacement(Vec2::xy(20.0, 1.0), Vec2::xy(80.0, 48.0));
+        assert_eq!(displacement, Vec2::xy(0.0, 3.0));
+        assert!(orientation.flip_x());
+    }
+
+    #[test]
+    fn klein_bottle_aabb_pieces() {
+        let klein = KleinBottle::new(100.0, 50.0);
+        let mut pieces = Vec::new();
+        klein.wrapped_aabbs(&AxisAlignmentBoundingBox::from_bounds(95.0, 105.0, 5.0, 15.0), &mut pieces);
+        assert_eq!(pieces.len(), 2);
+        assert_eq!((pieces[0].left(), pieces[0].right(), pieces[0].top()), (95.0, 100.0, 5.0));
+        assert_eq!((pieces[1].left(), pieces[1].right(), pieces[1].top(), pieces[1].bottom()), (0.0, 5.0, 35.0, 45.0));
+    }
+
+    #[test]
+    fn aabbs_taller_than_the_space_cover_the_whole_column() {
+        let tall = AxisAlignmentBoundingBox::from_bounds(35.0, 65.0, -5.0, 25.0);
+        let mut pieces = Vec::new();
+        KleinBottle::new(100.0, 20.0).wrapped_aabbs(&tall, &mut pieces);
+        TwistedTorus::new(100.0, 20.0, 7.0).wrapped_aabbs(&tall, &mut pieces);
+        assert!(pieces.iter().all(|piece| (piece.top(), piece.bottom()) == (0.0, 20.0)), "pieces: {:?}", pieces);
+    }
+}
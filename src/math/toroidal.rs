@@ -1,16 +1,15 @@
-use super::Vec2;
+use super::{Vector, Vec1, Vec2, Vec3, Real};
 use super::bounding::AxisAlignmentBoundingBox;
+use super::topology::{Confinement, Orientation};
+use super::lattice::{Lattice};
 
-/// Computes the minimal analogous distance in a toroidal space of the specified length.
+/// Computes the minimal analogous distance in a toroidal space of the specified length:
+/// the distance to the nearest image, whatever the number of lengths the distance spans.
 /// The maximum value for this minimal distance is never greater than length / 2.
-pub fn min_distance(distance: f32, length: u32) -> f32 {
-    let length = length as f32;
+pub fn min_distance(distance: Real, length: Real) -> Real {
     let half_length = length / 2.0;
-    if distance > half_length {
-        if distance <= length { distance - length } else { half_length }
-    }
-    else if distance < -half_length {
-        if distance >= -length { distance + length } else { -half_length }
+    if distance > half_length || distance < -half_length {
+        distance - (distance / length).round() * length
     }
     else {
         distance
@@ -19,61 +18,515 @@ pub fn min_distance(distance: f32, length: u32) -> f32 {
 
 /// Computes the minimal representation of a coordinate in a toroidal space of the specified length.
 /// The computed coordinate is always between 0 and length.
-pub fn min_coordinate(coordinate: f32, length: u32) -> f32 {
-    if coordinate >= 0.0 {
-        let uint_coordinate = coordinate as u32;
-        let mod_coordinate = (uint_coordinate % length) as f32 + coordinate - uint_coordinate as f32;
-        return if coordinate < 0.0 { mod_coordinate + length as f32} else { mod_coordinate }
+pub fn min_coordinate(coordinate: Real, length: Real) -> Real {
+    let mod_coordinate = coordinate.rem_euclid(length);
+    // Rounding can give exactly the length for tiny negative coordinates.
+    if mod_coordinate < length { mod_coordinate } else { 0.0 }
+}
+
+/// Behavior of the space at the two edges of an axis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Boundary {
+    /// The edges are glued: bodies leaving by one edge enter by the opposite one.
+    Periodic,
+
+    /// The edges are solid walls: bodies bounce with their restitution when their shape reaches them.
+    Reflecting,
+
+    /// The edges are open: bodies whose center leaves the space are removed from it.
+    Absorbing,
+}
+
+/// Similar to [`toroidal::min_distance()`](min_distance()) but for an axis with the specified boundary.
+pub fn axis_distance(distance: Real, length: Real, boundary: Boundary) -> Real {
+    match boundary {
+        Boundary::Periodic => min_distance(distance, length),
+        Boundary::Reflecting | Boundary::Absorbing => distance,
     }
-    else {
-        let coordinate = -coordinate;
-        let uint_coordinate = coordinate as u32;
-        let mod_coordinate = (uint_coordinate % length) as f32 + coordinate - uint_coordinate as f32;
-        let mod_coordinate = length as f32 - mod_coordinate;
-        return if coordinate < 0.0 { mod_coordinate + length as f32} else { mod_coordinate }
+}
+
+/// Similar to [`toroidal::min_coordinate()`](min_coordinate()) but for an axis with the specified boundary.
+/// The coordinates outside of a non periodic axis are clamped to the edges.
+pub fn axis_coordinate(coordinate: Real, length: Real, boundary: Boundary) -> Real {
+    match boundary {
+        Boundary::Periodic => min_coordinate(coordinate, length),
+        Boundary::Reflecting | Boundary::Absorbing => coordinate.max(0.0).min(length),
     }
 }
 
-/// A 2D toroidal bounds definition
-pub struct Bounds {
-    pub width: u32,
-    pub height: u32,
+/// Lees-Edwards sheared periodic boundaries (sliding bricks).
+/// The periodic images above the space move horizontally at `velocity`
+/// and the images below at `-velocity`, producing a simple shear flow.
+/// `offset` is the current horizontal displacement of the images above,
+/// advanced by the space in each step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LeesEdwards {
+    pub velocity: Real,
+    pub offset: Real,
+}
+
+/// A toroidal bounds definition of `D` dimensions, 2D by default:
+/// a ring in 1D, a rectangle in 2D and a rectangular cuboid in 3D.
+/// Each axis can be periodic (the default) or limited by walls or open edges.
+/// For example, a cylinder is periodic in x and reflecting in y.
+///
+/// The unit cell can also be skewed, defined by a [`Lattice`] (i.e. triclinic or hexagonal cells).
+/// Skewed cells are periodic in all the axes. Their positions are wrapped in fractional coordinates
+/// and their AABBs (see [`Bounds::get_toroidal_aabb()`]) are expressed in grid coordinates:
+/// the fractional coordinates multiplied by the widths of the cell.
+pub struct Bounds<const D: usize = 2> {
+    /// Length of each axis. For skewed cells, the distance between each pair of opposite faces.
+    pub lengths: Vector<D>,
+    pub boundaries: [Boundary; D],
+    pub lees_edwards: Option<LeesEdwards>,
+    lattice: Option<Lattice<D>>,
 }
 
+/// Axis along which the sheared images move.
+const FLOW_AXIS: usize = 0;
+
+/// Axis whose edges are sheared.
+const GRADIENT_AXIS: usize = 1;
+
 impl Bounds {
-    pub fn new(width: u32, height: u32) -> Bounds {
-        Bounds { width, height }
+    pub fn new(width: Real, height: Real) -> Bounds {
+        Bounds::with_lengths(Vec2::xy(width, height))
+    }
+
+    /// Periodic parallelogram cell whose edges are the two lattice vectors.
+    pub fn triclinic(a: Vec2, b: Vec2) -> Bounds {
+        Bounds::with_lattice(Lattice::new([a, b]))
+    }
+
+    /// Periodic rhombic cell of a hexagonal lattice of the given side.
+    pub fn hexagonal(side: Real) -> Bounds {
+        Bounds::with_lattice(Lattice::hexagonal(side))
+    }
+
+    /// A convenient method to set the boundary of each axis during the Bounds building.
+    pub fn with_boundaries(self, x_boundary: Boundary, y_boundary: Boundary) -> Bounds {
+        self.with_axis_boundary(0, x_boundary).with_axis_boundary(1, y_boundary)
+    }
+}
+
+impl Bounds<1> {
+    /// A 1D periodic space: a ring of the given length.
+    pub fn ring(length: Real) -> Bounds<1> {
+        Bounds::with_lengths(Vec1::x(length))
+    }
+}
+
+impl Bounds<3> {
+    /// A 3D periodic space: a 3-torus built from a rectangular cuboid.
+    pub fn volume(width: Real, height: Real, depth: Real) -> Bounds<3> {
+        Bounds::with_lengths(Vec3::xyz(width, height, depth))
+    }
+}
+
+impl<const D: usize> Bounds<D> {
+    /// Creates bounds with the given length in each axis, all of them periodic.
+    pub fn with_lengths(lengths: Vector<D>) -> Bounds<D> {
+        Bounds {
+            lengths,
+            boundaries: [Boundary::Periodic; D],
+            lees_edwards: None,
+            lattice: None,
+        }
+    }
+
+    /// Creates periodic bounds whose unit cell is the cell of the lattice.
+    pub fn with_lattice(lattice: Lattice<D>) -> Bounds<D> {
+        Bounds {
+            lattice: Some(lattice),
+            ..Bounds::with_lengths(lattice.widths())
+        }
+    }
+
+    /// The lattice of skewed bounds. `None` for rectangular bounds.
+    pub fn lattice(&self) -> Option<&Lattice<D>> {
+        self.lattice.as_ref()
+    }
+
+    /// A convenient method to set the boundary of one axis during the Bounds building.
+    /// Skewed bounds only support periodic boundaries, and sheared bounds need periodic x and y axes.
+    pub fn with_axis_boundary(mut self, axis: usize, boundary: Boundary) -> Bounds<D> {
+        assert!(
+            boundary == Boundary::Periodic || self.lattice.is_none(),
+            "Skewed bounds only support periodic boundaries"
+        );
+        assert!(
+            boundary == Boundary::Periodic || self.lees_edwards.is_none() || (axis != FLOW_AXIS && axis != GRADIENT_AXIS),
+            "Lees-Edwards boundaries require periodic x and y axes"
+        );
+        self.boundaries[axis] = boundary;
+        self
+    }
+
+    /// A convenient method to shear the bounds with Lees-Edwards boundaries during the Bounds building.
+    /// The images across the y edges move along the x axis.
+    /// The shear velocity is the relative velocity between the images above and the space.
+    /// The shear rate of the resulting flow is `shear_velocity / height`.
+    /// It requires at least two dimensions and both x and y axes to be periodic.
+    pub fn with_lees_edwards(mut self, shear_velocity: Real) -> Bounds<D> {
+        assert!(D >= 2, "Lees-Edwards boundaries require at least two dimensions");
+        assert!(self.lattice.is_none(), "Lees-Edwards boundaries require a rectangular cell");
+        assert!(
+            self.boundaries[FLOW_AXIS] == Boundary::Periodic && self.boundaries[GRADIENT_AXIS] == Boundary::Periodic,
+            "Lees-Edwards boundaries require periodic x and y axes"
+        );
+        self.lees_edwards = Some(LeesEdwards { velocity: shear_velocity, offset: 0.0 });
+        self
+    }
+
+    /// Advances the offset of the sheared images, if any.
+    pub fn advance_shear(&mut self, dt: Real) {
+        let width = self.lengths[FLOW_AXIS];
+        if let Some(lees_edwards) = &mut self.lees_edwards {
+            lees_edwards.offset = min_coordinate(lees_edwards.offset + lees_edwards.velocity * dt, width);
+        }
+    }
+
+    fn is_sheared(&self) -> bool {
+        self.lees_edwards.is_some() && self.boundaries[GRADIENT_AXIS] == Boundary::Periodic
+    }
+
+    /// Horizontal offset of the images above the space. Zero if the bounds are not sheared.
+    pub fn shear_offset(&self) -> Real {
+        match self.lees_edwards {
+            Some(lees_edwards) if self.is_sheared() => lees_edwards.offset,
+            _ => 0.0,
+        }
+    }
+
+    /// Horizontal velocity of the images above the space. Zero if the bounds are not sheared.
+    pub fn shear_velocity(&self) -> Real {
+        match self.lees_edwards {
+            Some(lees_edwards) if self.is_sheared() => lees_edwards.velocity,
+            _ => 0.0,
+        }
     }
 
-    /// Similar to [`toroidal::min_distance()`](min_distance()) but for a 2D toroidal space.
-    pub fn get_toroidal_distance(&self, distance: Vec2) -> Vec2 {
-        Vec2::xy(
-            min_distance(distance.x, self.width),
-            min_distance(distance.y, self.height),
-        )
+    /// Velocity of the nearest image of `to` seen from `from`, relative to the velocity of `to`.
+    /// It is only different from zero in sheared bounds, for images across the horizontal edges.
+    pub fn get_image_velocity(&self, distance: Vector<D>) -> Vector<D> {
+        if self.is_sheared() {
+            let turns = (distance[GRADIENT_AXIS] / self.lengths[GRADIENT_AXIS]).round();
+            Vector::axis(FLOW_AXIS, turns * self.shear_velocity())
+        }
+        else {
+            Vector::zero()
+        }
     }
 
-    /// Similar to [`toroidal::min_coordinate()`](min_coordinate())` but for a 2D toroidal space.
-    pub fn get_toroidal_position(&self, position: Vec2) -> Vec2 {
-        Vec2::xy(
-            min_coordinate(position.x, self.width),
-            min_coordinate(position.y, self.height),
-        )
+    /// Similar to [`toroidal::min_distance()`](min_distance()) but for a toroidal space.
+    /// Non periodic axes keep the distance as it is.
+    /// In sheared bounds, the images across the horizontal edges are displaced by the shear offset.
+    /// In skewed bounds, the nearest image is searched around the minimal fractional distance.
+    pub fn get_toroidal_distance(&self, distance: Vector<D>) -> Vector<D> {
+        if let Some(lattice) = &self.lattice {
+            return skewed_distance(lattice, distance)
+        }
+        let mut distance = distance;
+        if self.is_sheared() {
+            let turns = (distance[GRADIENT_AXIS] / self.lengths[GRADIENT_AXIS]).round();
+            distance[FLOW_AXIS] -= turns * self.shear_offset();
+            distance[GRADIENT_AXIS] -= turns * self.lengths[GRADIENT_AXIS];
+        }
+        distance.map(|axis, component| axis_distance(component, self.lengths[axis], self.boundaries[axis]))
     }
 
-    /// Similar to [`toroidal::min_coordinate()`](min_coordinate())` but for an AABB.
-    pub fn get_toroidal_aabb(&self, aabb: &AxisAlignmentBoundingBox) -> AxisAlignmentBoundingBox {
-        AxisAlignmentBoundingBox::from_bounds(
-            min_coordinate(aabb.left(), self.width),
-            min_coordinate(aabb.right(), self.width),
-            min_coordinate(aabb.top(), self.height),
-            min_coordinate(aabb.bottom(), self.height),
-        )
+    /// Similar to [`toroidal::min_coordinate()`](min_coordinate())` but for a toroidal space.
+    /// Non periodic axes clamp the position to the edges.
+    pub fn get_toroidal_position(&self, position: Vector<D>) -> Vector<D> {
+        if let Some(lattice) = &self.lattice {
+            let fractional = lattice.to_fractional(position).map(|_, coordinate| min_coordinate(coordinate, 1.0));
+            return lattice.to_cartesian(fractional)
+        }
+        let sheared = self.unshear(position).0;
+        sheared.map(|axis, component| axis_coordinate(component, self.lengths[axis], self.boundaries[axis]))
+    }
+
+    /// Number of times that a coordinate turns around the length of its axis.
+    fn turns(&self, axis: usize, coordinate: Real) -> Real {
+        match self.boundaries[axis] {
+            Boundary::Periodic => (coordinate / self.lengths[axis]).floor(),
+            Boundary::Reflecting | Boundary::Absorbing => 0.0,
+        }
+    }
+
+    /// Moves the horizontal coordinate of a position beyond the horizontal edges to the sheared images
+    /// of the space. Returns it with the number of turns around the horizontal edges.
+    fn unshear(&self, position: Vector<D>) -> (Vector<D>, Real) {
+        if D < 2 {
+            return (position, 0.0)
+        }
+        let turns = self.turns(GRADIENT_AXIS, position[GRADIENT_AXIS]);
+        (position - Vector::axis(FLOW_AXIS, turns * self.shear_offset()), turns)
+    }
+
+    /// Grid coordinates of a position: the position itself in rectangular bounds
+    /// and the fractional coordinates multiplied by the widths of the cell in skewed bounds.
+    /// The grid coordinates of the positions inside the bounds are inside the box of dimension [`Bounds::dimension()`].
+    pub fn get_grid_position(&self, position: Vector<D>) -> Vector<D> {
+        match &self.lattice {
+            Some(lattice) => lattice.to_fractional(position).map(|axis, coordinate| coordinate * self.lengths[axis]),
+            None => position,
+        }
+    }
+
+    /// AABB in grid coordinates (see [`Bounds::get_grid_position()`]) that contains the given AABB.
+    pub fn get_grid_aabb(&self, aabb: &AxisAlignmentBoundingBox<D>) -> AxisAlignmentBoundingBox<D> {
+        match &self.lattice {
+            Some(lattice) => {
+                let half_dimension = lattice.fractional_half_extent(aabb.half_dimension())
+                    .map(|axis, extent| extent * self.lengths[axis]);
+                AxisAlignmentBoundingBox::new(self.get_grid_position(aabb.position()), half_dimension)
+            },
+            None => AxisAlignmentBoundingBox::from_corners(aabb.min(), aabb.max()),
+        }
+    }
+
+    /// Similar to [`toroidal::min_coordinate()`](min_coordinate())` but for an AABB in grid coordinates.
+    /// Non periodic axes clamp the AABB to the edges, so it never wraps around them.
+    /// An AABB as wide as a periodic axis covers the whole axis, from 0 to its length.
+    pub fn get_toroidal_aabb(&self, aabb: &AxisAlignmentBoundingBox<D>) -> AxisAlignmentBoundingBox<D> {
+        let covers = |axis| {
+            self.boundaries[axis] == Boundary::Periodic && aabb.max()[axis] - aabb.min()[axis] >= self.lengths[axis]
+        };
+        let min = aabb.min().map(|axis, component| match covers(axis) {
+            true => 0.0,
+            false => axis_coordinate(component, self.lengths[axis], self.boundaries[axis]),
+        });
+        let max = aabb.max().map(|axis, component| match covers(axis) {
+            true => self.lengths[axis],
+            false => axis_coordinate(component, self.lengths[axis], self.boundaries[axis]),
+        });
+        AxisAlignmentBoundingBox::from_corners(min, max)
+    }
+
+    /// Applies the boundary of each axis to a position that could be outside the bounds.
+    /// Positions whose half extent goes beyond a reflecting edge are mirrored inside,
+    /// marking the axis in the returned reflection.
+    /// Positions beyond an absorbing edge are absorbed.
+    /// In sheared bounds, crossing the horizontal edges shifts the horizontal position and velocity.
+    pub fn confine(&self, position: Vector<D>, half_extent: Vector<D>) -> Confinement<D> {
+        if let Some(lattice) = &self.lattice {
+            let fractional = lattice.to_fractional(position);
+            let mut winding = [0; D];
+            for (axis, turns) in winding.iter_mut().enumerate() {
+                *turns = fractional[axis].floor() as i32;
+            }
+            return Confinement::Inside {
+                position: self.get_toroidal_position(position),
+                orientation: Orientation::identity(),
+                reflection: Orientation::identity(),
+                velocity_shift: Vector::zero(),
+                winding,
+            }
+        }
+        let (sheared, turns) = self.unshear(position);
+        let mut confined = Vector::zero();
+        let mut reflection = [false; D];
+        let mut winding = [0; D];
+        for axis in 0..D {
+            match confine_coordinate(sheared[axis], half_extent[axis], self.lengths[axis], self.boundaries[axis]) {
+                Some((coordinate, reflected)) => {
+                    confined[axis] = coordinate;
+                    reflection[axis] = reflected;
+                    winding[axis] = self.turns(axis, sheared[axis]) as i32;
+                },
+                None => return Confinement::Absorbed,
+            }
+        }
+        Confinement::Inside {
+            position: confined,
+            orientation: Orientation::identity(),
+            reflection: Orientation::from_flips(reflection),
+            velocity_shift: if turns != 0.0 { Vector::axis(FLOW_AXIS, -turns * self.shear_velocity()) } else { Vector::zero() },
+            winding,
+        }
     }
 
     /// Returns the bounds dimension value as a float vector.
-    pub fn dimension(&self) -> Vec2 {
-        Vec2::xy(self.width as f32, self.height as f32)
+    /// For skewed cells, it is the distance between each pair of opposite faces.
+    pub fn dimension(&self) -> Vector<D> {
+        self.lengths
+    }
+
+    /// Length, area or volume of the unit cell.
+    pub fn cell_volume(&self) -> Real {
+        match &self.lattice {
+            Some(lattice) => lattice.volume(),
+            None => self.lengths.product(),
+        }
+    }
+
+    /// Translation between a position and its image after the given number of turns around each axis.
+    pub fn get_image_translation(&self, winding: [i32; D]) -> Vector<D> {
+        let turns = Vector::from_fn(|axis| winding[axis] as Real);
+        match &self.lattice {
+            Some(lattice) => lattice.to_cartesian(turns),
+            None => turns.map(|axis, turns| turns * self.lengths[axis]),
+        }
+    }
+
+    /// Translations of every periodic image of the AABB that intersects the viewport,
+    /// including the AABB itself (a zero translation) if it intersects the viewport.
+    /// Both AABBs are plain boxes in space coordinates: they do not wrap around the edges
+    /// and can be larger than the bounds.
+    /// In sheared bounds, the images across the horizontal edges are shifted by the shear offset.
+    pub fn get_images(&self, aabb: &AxisAlignmentBoundingBox<D>, viewport: &AxisAlignmentBoundingBox<D>) -> Vec<Vector<D>> {
+        let mut images = Vec::new();
+        self.for_each_image(aabb, viewport, |translation| images.push(translation));
+        images
+    }
+
+    /// Calls the function with the same translations as [`Bounds::get_images()`], without allocating them.
+    pub fn for_each_image<F>(&self, aabb: &AxisAlignmentBoundingBox<D>, viewport: &AxisAlignmentBoundingBox<D>, mut function: F)
+    where F: FnMut(Vector<D>) {
+        let intersects = |translation: Vector<D>| (0..D).all(|axis| {
+            aabb.min()[axis] + translation[axis] < viewport.max()[axis]
+                && aabb.max()[axis] + translation[axis] > viewport.min()[axis]
+        });
+
+        if let Some(lattice) = &self.lattice {
+            // Candidates from the fractional extents of both boxes, checked one by one.
+            let extent = |aabb: &AxisAlignmentBoundingBox<D>| {
+                let center = lattice.to_fractional(aabb.position());
+                let half_extent = lattice.fractional_half_extent(aabb.half_dimension());
+                (center - half_extent, center + half_extent)
+            };
+            let ((aabb_min, aabb_max), (viewport_min, viewport_max)) = (extent(aabb), extent(viewport));
+            let mut first = [0; D];
+            let mut last = [0; D];
+            for axis in 0..D {
+                first[axis] = (viewport_min[axis] - aabb_max[axis]).ceil() as i32;
+                last[axis] = (viewport_max[axis] - aabb_min[axis]).floor() as i32;
+            }
+            for_each_turns(first, last, |turns| {
+                let translation = lattice.to_cartesian(Vector::from_fn(|axis| turns[axis] as Real));
+                if intersects(translation) {
+                    function(translation);
+                }
+            });
+            return
+        }
+
+        let turns_range = |axis: usize, shift: Real| match self.boundaries[axis] {
+            Boundary::Periodic => {
+                let length = self.lengths[axis];
+                (
+                    ((viewport.min()[axis] - aabb.max()[axis] - shift) / length).ceil() as i32,
+                    ((viewport.max()[axis] - aabb.min()[axis] - shift) / length).floor() as i32,
+                )
+            },
+            Boundary::Reflecting | Boundary::Absorbing => (0, 0),
+        };
+
+        let sheared = self.is_sheared();
+        let mut first = [0; D];
+        let mut last = [0; D];
+        for axis in 0..D {
+            if !sheared || axis != FLOW_AXIS {
+                let (axis_first, axis_last) = turns_range(axis, 0.0);
+                first[axis] = axis_first;
+                last[axis] = axis_last;
+            }
+        }
+
+        for_each_turns(first, last, |turns| {
+            let mut translation = Vector::from_fn(|axis| turns[axis] as Real * self.lengths[axis]);
+            if sheared {
+                // The horizontal range depends on the shift of the row of images.
+                let shift = turns[GRADIENT_AXIS] as Real * self.shear_offset();
+                let (first, last) = turns_range(FLOW_AXIS, shift);
+                for flow_turns in first..=last {
+                    translation[FLOW_AXIS] = flow_turns as Real * self.lengths[FLOW_AXIS] + shift;
+                    if intersects(translation) {
+                        function(translation);
+                    }
+                }
+            }
+            else if intersects(translation) {
+                function(translation);
+            }
+        });
+    }
+
+    /// Changes the dimension of the bounds.
+    /// Skewed cells scale each lattice vector by the change of the width of its axis.
+    pub fn resize(&mut self, dimension: Vector<D>) {
+        if let Some(lees_edwards) = &mut self.lees_edwards {
+            lees_edwards.offset *= dimension[FLOW_AXIS] / self.lengths[FLOW_AXIS];
+        }
+        let lengths = self.lengths;
+        if let Some(lattice) = &mut self.lattice {
+            *lattice = lattice.scaled(dimension.map(|axis, length| length / lengths[axis]));
+        }
+        self.lengths = dimension;
+    }
+}
+
+/// Calls the function with every combination of turns between the first and the last ones (both included).
+fn for_each_turns<F, const D: usize>(first: [i32; D], last: [i32; D], mut function: F)
+where F: FnMut([i32; D]) {
+    if (0..D).any(|axis| first[axis] > last[axis]) {
+        return
+    }
+    let mut turns = first;
+    'turns: loop {
+        function(turns);
+        for axis in 0..D {
+            turns[axis] += 1;
+            if turns[axis] <= last[axis] {
+                continue 'turns
+            }
+            turns[axis] = first[axis];
+        }
+        return
+    }
+}
+
+/// Minimum image distance in a skewed cell.
+/// The minimal fractional distance is not always the shortest one,
+/// so the neighbor images around it are also checked.
+fn skewed_distance<const D: usize>(lattice: &Lattice<D>, distance: Vector<D>) -> Vector<D> {
+    let fractional = lattice.to_fractional(distance).map(|_, coordinate| min_distance(coordinate, 1.0));
+    let minimal = lattice.to_cartesian(fractional);
+    if lattice.is_orthogonal() {
+        return minimal
+    }
+
+    let mut nearest = minimal;
+    let mut offsets = [-1; D];
+    'images: loop {
+        let offset = Vector::from_fn(|axis| offsets[axis] as Real);
+        let candidate = lattice.to_cartesian(fractional - offset);
+        if candidate.square_length() < nearest.square_length() {
+            nearest = candidate;
+        }
+        for offset in offsets.iter_mut() {
+            *offset += 1;
+            if *offset <= 1 {
+                continue 'images
+            }
+            *offset = -1;
+        }
+        return nearest
+    }
+}
+
+fn confine_coordinate(coordinate: Real, half_extent: Real, length: Real, boundary: Boundary) -> Option<(Real, bool)> {
+    // The walls stop the center at the half extent from the edges, or in the middle if the body is wider.
+    let (low, high) = (half_extent.min(length / 2.0), (length - half_extent).max(length / 2.0));
+    match boundary {
+        Boundary::Periodic => Some((min_coordinate(coordinate, length), false)),
+        Boundary::Reflecting if coordinate < low || coordinate > high => {
+            let mirrored = if coordinate < low { 2.0 * low - coordinate } else { 2.0 * high - coordinate };
+            Some((mirrored.max(low).min(high), true))
+        },
+        Boundary::Absorbing if coordinate < 0.0 || coordinate > length => None,
+        _ => Some((coordinate, false)),
     }
 }
 
@@ -83,33 +536,162 @@ mod tests {
 
     #[test]
     fn positive_coordinate() {
-        assert_eq!(min_coordinate(12.5, 20), 12.5);
-        assert_eq!(min_coordinate(32.5, 20), 12.5);
-        assert_eq!(min_coordinate(52.5, 20), 12.5);
+        assert_eq!(min_coordinate(12.5, 20.0), 12.5);
+        assert_eq!(min_coordinate(32.5, 20.0), 12.5);
+        assert_eq!(min_coordinate(52.5, 20.0), 12.5);
     }
 
     #[test]
     fn negative_coordinate() {
-        assert_eq!(min_coordinate(-12.5, 20), 7.5);
-        assert_eq!(min_coordinate(-32.5, 20), 7.5);
-        assert_eq!(min_coordinate(-52.5, 20), 7.5);
+        assert_eq!(min_coordinate(-12.5, 20.0), 7.5);
+        assert_eq!(min_coordinate(-32.5, 20.0), 7.5);
+        assert_eq!(min_coordinate(-52.5, 20.0), 7.5);
     }
 
     #[test]
     fn positive_distance() {
-        assert_eq!(min_distance(7.5, 20), 7.5);
-        assert_eq!(min_distance(12.5, 20), -7.5);
-        assert_eq!(min_distance(10.0, 20), 10.0);
-        assert_eq!(min_distance(20.0, 20), 0.0);
-        assert_eq!(min_distance(27.5, 20), 10.0);
+        assert_eq!(min_distance(7.5, 20.0), 7.5);
+        assert_eq!(min_distance(12.5, 20.0), -7.5);
+        assert_eq!(min_distance(10.0, 20.0), 10.0);
+        assert_eq!(min_distance(20.0, 20.0), 0.0);
+        assert_eq!(min_distance(27.5, 20.0), 7.5);
+        assert_eq!(min_distance(52.5, 20.0), -7.5);
     }
 
     #[test]
     fn negative_distance() {
-        assert_eq!(min_distance(-7.5, 20), -7.5);
-        assert_eq!(min_distance(-12.5, 20), 7.5);
-        assert_eq!(min_distance(-10.0, 20), -10.0);
-        assert_eq!(min_distance(-20.0, 20), -0.0);
-        assert_eq!(min_distance(-27.5, 20), -10.0);
+        assert_eq!(min_distance(-7.5, 20.0), -7.5);
+        assert_eq!(min_distance(-12.5, 20.0), 7.5);
+        assert_eq!(min_distance(-10.0, 20.0), -10.0);
+        assert_eq!(min_distance(-20.0, 20.0), -0.0);
+        assert_eq!(min_distance(-27.5, 20.0), -7.5);
+        assert_eq!(min_distance(-52.5, 20.0), 7.5);
+    }
+
+    #[test]
+    fn unit_length_coordinate() {
+        assert_eq!(min_coordinate(1.25, 1.0), 0.25);
+        assert_eq!(min_coordinate(-0.25, 1.0), 0.75);
+        assert_eq!(min_distance(0.75, 1.0), -0.25);
+    }
+
+    #[test]
+    fn cylinder_distance() {
+        let bounds = Bounds::new(20.0, 20.0).with_boundaries(Boundary::Periodic, Boundary::Reflecting);
+        assert_eq!(bounds.get_toroidal_distance(Vec2::xy(15.0, 15.0)), Vec2::xy(-5.0, 15.0));
+    }
+
+    #[test]
+    fn reflecting_and_absorbing_confinement() {
+        let bounds = Bounds::new(20.0, 20.0).with_boundaries(Boundary::Reflecting, Boundary::Absorbing);
+        match bounds.confine(Vec2::xy(22.0, 5.0), Vec2::zero()) {
+            Confinement::Inside { position, reflection, .. } => {
+                assert_eq!(position, Vec2::xy(18.0, 5.0));
+                assert_eq!(reflection, Orientation::new(true, false));
+            },
+            Confinement::Absorbed => panic!("Expected a reflection"),
+        }
+        assert_eq!(bounds.confine(Vec2::xy(5.0, -1.0), Vec2::zero()), Confinement::Absorbed);
+
+        // The shape bounces when it reaches the wall, and only the center is absorbed.
+        match bounds.confine(Vec2::xy(19.0, 19.0), Vec2::xy(2.0, 2.0)) {
+            Confinement::Inside { position, reflection, .. } => {
+                assert_eq!(position, Vec2::xy(17.0, 19.0));
+                assert_eq!(reflection, Orientation::new(true, false));
+            },
+            Confinement::Absorbed => panic!("Expected a reflection"),
+        }
+    }
+
+    #[test]
+    fn ring_and_volume() {
+        let ring = Bounds::ring(20.0);
+        assert_eq!(ring.get_toroidal_position(Vec1::x(-5.0)), Vec1::x(15.0));
+        assert_eq!(ring.get_toroidal_distance(Vec1::x(15.0)), Vec1::x(-5.0));
+
+        let volume = Bounds::volume(20.0, 20.0, 10.0).with_axis_boundary(1, Boundary::Reflecting);
+        assert_eq!(volume.get_toroidal_distance(Vec3::xyz(15.0, 15.0, 8.0)), Vec3::xyz(-5.0, 15.0, -2.0));
+        match volume.confine(Vec3::xyz(21.0, 5.0, -1.0), Vec3::zero()) {
+            Confinement::Inside { position, winding, .. } => {
+                assert_eq!(position, Vec3::xyz(1.0, 5.0, 9.0));
+                assert_eq!(winding, [1, 0, -1]);
+            },
+            Confinement::Absorbed => panic!("Expected a wrapped position"),
+        }
+    }
+
+    #[test]
+    fn lees_edwards_images() {
+        let mut bounds = Bounds::new(20.0, 20.0).with_lees_edwards(4.0);
+        bounds.advance_shear(1.5);
+        assert_eq!(bounds.shear_offset(), 6.0);
+
+        // Body near the top seen from a body near the bottom: its image below is shifted by -offset.
+        assert_eq!(bounds.get_toroidal_distance(Vec2::xy(10.0, 2.0) - Vec2::xy(16.0, 19.0)), Vec2::xy(0.0, 3.0));
+        assert_eq!(bounds.get_image_velocity(Vec2::xy(10.0, 2.0) - Vec2::xy(16.0, 19.0)), Vec2::x(-4.0));
+
+        match bounds.confine(Vec2::xy(3.0, 21.0), Vec2::zero()) {
+            Confinement::Inside { position, velocity_shift, .. } => {
+                assert_eq!(position, Vec2::xy(17.0, 1.0));
+                assert_eq!(velocity_shift, Vec2::x(-4.0));
+            },
+            Confinement::Absorbed => panic!("Expected a wrapped position"),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Lees-Edwards boundaries require periodic x and y axes")]
+    fn lees_edwards_over_walls() {
+        Bounds::new(20.0, 20.0).with_boundaries(Boundary::Periodic, Boundary::Reflecting).with_lees_edwards(4.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Lees-Edwards boundaries require periodic x and y axes")]
+    fn walls_over_lees_edwards() {
+        Bounds::new(20.0, 20.0).with_lees_edwards(4.0).with_axis_boundary(0, Boundary::Absorbing);
+    }
+
+    #[test]
+    fn hexagonal_cell() {
+        let bounds = Bounds::hexagonal(10.0);
+        let height = 5.0 * (3.0 as Real).sqrt();
+        assert!((bounds.cell_volume() - 10.0 * height).abs() < 1e-3);
+
+        // Beyond the slanted right edge: wrapped by the first lattice vector, inside the rhombus.
+        let position = bounds.get_toroidal_position(Vec2::xy(16.0, height / 2.0));
+        assert!((position - Vec2::xy(6.0, height / 2.0)).length() < 1e-3);
+
+        // The opposite corners of the acute angles are neighbors through the lattice.
+        let distance = bounds.get_toroidal_distance(Vec2::xy(14.5, height - 0.5) - Vec2::xy(0.5, 0.5));
+        assert!((distance - Vec2::xy(-1.0, height - 1.0 - height)).length() < 1e-3, "distance: {:?}", distance);
+    }
+
+    #[test]
+    fn images_in_viewport() {
+        let bounds = Bounds::new(20.0, 10.0);
+        let viewport = AxisAlignmentBoundingBox::from_bounds(0.0, 20.0, 0.0, 10.0);
+
+        // A body in a corner is seen in the four corners of the viewport.
+        let corner = AxisAlignmentBoundingBox::new(Vec2::xy(19.0, 9.0), Vec2::xy(2.0, 2.0));
+        assert_eq!(bounds.get_images(&corner, &viewport).len(), 4);
+
+        // A viewport three times larger than the world in each axis.
+        let large_viewport = AxisAlignmentBoundingBox::from_bounds(-20.0, 40.0, -10.0, 20.0);
+        let center = AxisAlignmentBoundingBox::new(Vec2::xy(10.0, 5.0), Vec2::xy(1.0, 1.0));
+        assert_eq!(bounds.get_images(&center, &large_viewport).len(), 9);
+
+        // A body larger than the world overlaps the viewport with several images along each axis.
+        let huge = AxisAlignmentBoundingBox::new(Vec2::xy(10.0, 5.0), Vec2::xy(15.0, 2.0));
+        let mut images = bounds.get_images(&huge, &viewport);
+        images.sort_by(|a, b| a.x.partial_cmp(&b.x).unwrap());
+        assert_eq!(images, vec![Vec2::x(-20.0), Vec2::zero(), Vec2::x(20.0)]);
+
+        // Sheared rows of images: the row below is shifted by -4.
+        let mut sheared = Bounds::new(20.0, 10.0).with_lees_edwards(4.0);
+        sheared.advance_shear(1.0);
+        let images = sheared.get_images(&AxisAlignmentBoundingBox::new(Vec2::xy(1.0, 1.0), Vec2::xy(0.5, 0.5)), &viewport);
+        assert_eq!(images, vec![Vec2::zero()]);
+        let images = sheared.get_images(&AxisAlignmentBoundingBox::new(Vec2::xy(1.0, 9.5), Vec2::xy(1.0, 1.0)), &viewport);
+        assert_eq!(images, vec![Vec2::xy(16.0, -10.0), Vec2::zero()]);
     }
 }
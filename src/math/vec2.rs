@@ -1,129 +0,0 @@
-use std::ops::{Add, Sub, Mul, Div, Neg, AddAssign, SubAssign, MulAssign, DivAssign};
-
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub struct Vec2 {
-    pub x: f32,
-    pub y: f32,
-}
-
-impl Vec2 {
-    pub fn zero() -> Vec2 {
-        Vec2 {x: 0.0, y: 0.0}
-    }
-
-    pub fn xy(x: f32, y: f32) -> Vec2 {
-        Vec2 {x, y}
-    }
-
-    pub fn x(x: f32) -> Vec2 {
-        Vec2 {x, y: 0.0}
-    }
-
-    pub fn y(y: f32) -> Vec2 {
-        Vec2 {x: 0.0, y}
-    }
-
-    pub fn from_angle(angle: f32) -> Vec2 {
-        Vec2 {x: angle.cos(), y: angle.sin()}
-    }
-
-    pub fn clear(&mut self) {
-        self.x = 0.0;
-        self.y = 0.0;
-    }
-
-    pub fn length(&self) -> f32 {
-        (self.x * self.x + self.y * self.y).sqrt()
-    }
-
-    pub fn square_length(&self) -> f32 {
-        self.x * self.x + self.y * self.y
-    }
-}
-
-impl Add for Vec2 {
-    type Output = Vec2;
-
-    fn add(self, other: Vec2) -> Vec2 {
-        Vec2 {
-            x: self.x + other.x,
-            y: self.y + other.y,
-        }
-    }
-}
-
-impl Sub for Vec2 {
-    type Output = Vec2;
-
-    fn sub(self, other: Vec2) -> Vec2 {
-        Vec2 {
-            x: self.x - other.x,
-            y: self.y - other.y,
-        }
-    }
-}
-
-impl Mul for Vec2 {
-    type Output = f32;
-
-    fn mul(self, other: Vec2) -> f32 {
-        self.x * other.x + self.y * other.y
-    }
-}
-
-impl Mul<f32> for Vec2 {
-    type Output = Vec2;
-
-    fn mul(self, scalar: f32) -> Vec2 {
-        Vec2 {
-            x: self.x * scalar,
-            y: self.y * scalar,
-        }
-    }
-}
-
-impl Div<f32> for Vec2 {
-    type Output = Vec2;
-
-    fn div(self, scalar: f32) -> Vec2 {
-        Vec2 {
-            x: self.x / scalar,
-            y: self.y / scalar,
-        }
-    }
-}
-
-impl Neg for Vec2 {
-    type Output = Vec2;
-
-    fn neg(self) -> Vec2 {
-        Vec2 {
-            x: -self.x,
-            y: -self.y,
-        }
-    }
-}
-
-impl AddAssign for Vec2 {
-    fn add_assign(&mut self, other: Vec2) {
-        *self = *self + other
-    }
-}
-
-impl SubAssign for Vec2 {
-    fn sub_assign(&mut self, other: Vec2) {
-        *self = *self - other
-    }
-}
-
-impl MulAssign<f32> for Vec2 {
-    fn mul_assign(&mut self, scalar: f32) {
-        *self = *self * scalar
-    }
-}
-
-impl DivAssign<f32> for Vec2 {
-    fn div_assign(&mut self, scalar: f32) {
-        *self = *self / scalar
-    }
-}
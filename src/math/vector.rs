@@ -0,0 +1,266 @@
+use super::Real;
+use super::functions::{sin, cos};
+
+use std::ops::{Add, Sub, Mul, Div, Neg, AddAssign, SubAssign, MulAssign, DivAssign, Index, IndexMut, Deref, DerefMut};
+
+/// Vector of `D` real components.
+/// The components of the 1D, 2D and 3D vectors can be accessed by name (`x`, `y`, `z`)
+/// and the components of any vector by index.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(transparent)]
+pub struct Vector<const D: usize>([Real; D]);
+
+pub type Vec1 = Vector<1>;
+pub type Vec2 = Vector<2>;
+pub type Vec3 = Vector<3>;
+
+impl<const D: usize> Vector<D> {
+    pub fn zero() -> Vector<D> {
+        Vector([0.0; D])
+    }
+
+    pub fn from_array(components: [Real; D]) -> Vector<D> {
+        Vector(components)
+    }
+
+    /// Vector whose component of each axis is computed by the function.
+    pub fn from_fn<F>(mut function: F) -> Vector<D>
+    where F: FnMut(usize) -> Real {
+        Vector::<D>::zero().map(|axis, _| function(axis))
+    }
+
+    /// Vector whose components are all the same value.
+    pub fn splat(value: Real) -> Vector<D> {
+        Vector([value; D])
+    }
+
+    /// Vector with the value in the component of the given axis and zero in the rest.
+    pub fn axis(axis: usize, value: Real) -> Vector<D> {
+        let mut vector = Vector::zero();
+        vector.0[axis] = value;
+        vector
+    }
+
+    pub fn components(&self) -> [Real; D] {
+        self.0
+    }
+
+    pub fn clear(&mut self) {
+        self.0 = [0.0; D];
+    }
+
+    pub fn length(&self) -> Real {
+        self.square_length().sqrt()
+    }
+
+    pub fn square_length(&self) -> Real {
+        *self * *self
+    }
+
+    /// Applies a function to each component.
+    pub fn map<F>(&self, mut function: F) -> Vector<D>
+    where F: FnMut(usize, Real) -> Real {
+        let mut vector = *self;
+        for (axis, component) in vector.0.iter_mut().enumerate() {
+            *component = function(axis, *component);
+        }
+        vector
+    }
+
+    /// Product of the components. The length, area or volume of a box with this dimension.
+    pub fn product(&self) -> Real {
+        self.0.iter().product()
+    }
+
+    pub fn min_component(&self) -> Real {
+        self.0.iter().cloned().fold(Real::INFINITY, Real::min)
+    }
+}
+
+impl Vector<1> {
+    pub fn x(x: Real) -> Vec1 {
+        Vector([x])
+    }
+}
+
+impl Vector<2> {
+    pub fn xy(x: Real, y: Real) -> Vec2 {
+        Vector([x, y])
+    }
+
+    pub fn x(x: Real) -> Vec2 {
+        Vector([x, 0.0])
+    }
+
+    pub fn y(y: Real) -> Vec2 {
+        Vector([0.0, y])
+    }
+
+    pub fn from_angle(angle: Real) -> Vec2 {
+        Vector([cos(angle), sin(angle)])
+    }
+}
+
+impl Vector<3> {
+    pub fn xyz(x: Real, y: Real, z: Real) -> Vec3 {
+        Vector([x, y, z])
+    }
+
+    pub fn x(x: Real) -> Vec3 {
+        Vector([x, 0.0, 0.0])
+    }
+
+    pub fn y(y: Real) -> Vec3 {
+        Vector([0.0, y, 0.0])
+    }
+
+    pub fn z(z: Real) -> Vec3 {
+        Vector([0.0, 0.0, z])
+    }
+}
+
+/// Named component of a 1D vector.
+#[repr(C)]
+pub struct X {
+    pub x: Real,
+}
+
+/// Named components of a 2D vector.
+#[repr(C)]
+pub struct XY {
+    pub x: Real,
+    pub y: Real,
+}
+
+/// Named components of a 3D vector.
+#[repr(C)]
+pub struct XYZ {
+    pub x: Real,
+    pub y: Real,
+    pub z: Real,
+}
+
+macro_rules! named_components {
+    ($dimension:expr, $names:ident) => {
+        impl Deref for Vector<$dimension> {
+            type Target = $names;
+
+            fn deref(&self) -> &$names {
+                // Safe: both types are a sequence of $dimension reals with the same layout.
+                unsafe { &*(self.0.as_ptr() as *const $names) }
+            }
+        }
+
+        impl DerefMut for Vector<$dimension> {
+            fn deref_mut(&mut self) -> &mut $names {
+                // Safe: both types are a sequence of $dimension reals with the same layout.
+                unsafe { &mut *(self.0.as_mut_ptr() as *mut $names) }
+            }
+        }
+    };
+}
+
+named_components!(1, X);
+named_components!(2, XY);
+named_components!(3, XYZ);
+
+impl<const D: usize> Index<usize> for Vector<D> {
+    type Output = Real;
+
+    fn index(&self, axis: usize) -> &Real {
+        &self.0[axis]
+    }
+}
+
+impl<const D: usize> IndexMut<usize> for Vector<D> {
+    fn index_mut(&mut self, axis: usize) -> &mut Real {
+        &mut self.0[axis]
+    }
+}
+
+impl<const D: usize> Add for Vector<D> {
+    type Output = Vector<D>;
+
+    fn add(self, other: Vector<D>) -> Vector<D> {
+        self.map(|axis, component| component + other.0[axis])
+    }
+}
+
+impl<const D: usize> Sub for Vector<D> {
+    type Output = Vector<D>;
+
+    fn sub(self, other: Vector<D>) -> Vector<D> {
+        self.map(|axis, component| component - other.0[axis])
+    }
+}
+
+impl<const D: usize> Mul for Vector<D> {
+    type Output = Real;
+
+    fn mul(self, other: Vector<D>) -> Real {
+        self.0.iter().zip(other.0.iter()).map(|(a, b)| a * b).sum()
+    }
+}
+
+impl<const D: usize> Mul<Real> for Vector<D> {
+    type Output = Vector<D>;
+
+    fn mul(self, scalar: Real) -> Vector<D> {
+        self.map(|_, component| component * scalar)
+    }
+}
+
+impl<const D: usize> Div<Real> for Vector<D> {
+    type Output = Vector<D>;
+
+    fn div(self, scalar: Real) -> Vector<D> {
+        self.map(|_, component| component / scalar)
+    }
+}
+
+impl<const D: usize> Neg for Vector<D> {
+    type Output = Vector<D>;
+
+    fn neg(self) -> Vector<D> {
+        self.map(|_, component| -component)
+    }
+}
+
+impl<const D: usize> AddAssign for Vector<D> {
+    fn add_assign(&mut self, other: Vector<D>) {
+        *self = *self + other
+    }
+}
+
+impl<const D: usize> SubAssign for Vector<D> {
+    fn sub_assign(&mut self, other: Vector<D>) {
+        *self = *self - other
+    }
+}
+
+impl<const D: usize> MulAssign<Real> for Vector<D> {
+    fn mul_assign(&mut self, scalar: Real) {
+        *self = *self * scalar
+    }
+}
+
+impl<const D: usize> DivAssign<Real> for Vector<D> {
+    fn div_assign(&mut self, scalar: Real) {
+        *self = *self / scalar
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn named_and_indexed_components() {
+        let mut vector = Vec3::xyz(1.0, 2.0, 3.0);
+        vector.z = 4.0;
+        assert_eq!(vector[2], 4.0);
+        assert_eq!(vector.x + vector.y, 3.0);
+        assert_eq!(vector * Vec3::splat(1.0), 7.0);
+        assert_eq!((Vec1::x(3.0) - Vec1::x(5.0)).length(), 2.0);
+    }
+}
@@ -0,0 +1,119 @@
+use super::math::{Vector, Real, topology::Topology};
+use super::body::{Body};
+use super::shapes::{Shape};
+
+/// Verlet neighbor list: the pairs of bodies whose shapes are closer than a skin distance.
+/// While no body moves more than half the skin, no pair out of the list can come into contact,
+/// so the list can be reused instead of asking the broad phase again.
+pub struct NeighborList<const D: usize = 2> {
+    skin: Real,
+    pairs: Vec<(usize, usize)>,
+    positions: Vec<Vector<D>>, // positions of the bodies at the last build
+    inverse_masses: Vec<Real>, // inverse masses of the bodies at the last build
+    radii: Vec<Real>, // radii of the bodies at the last build
+    valid: bool,
+}
+
+impl<const D: usize> NeighborList<D> {
+    pub fn new(skin: Real) -> NeighborList<D> {
+        NeighborList {
+            skin,
+            pairs: Vec::new(),
+            positions: Vec::new(),
+            inverse_masses: Vec::new(),
+            radii: Vec::new(),
+            valid: false,
+        }
+    }
+
+    pub fn skin(&self) -> Real {
+        self.skin
+    }
+
+    /// Pairs found in the last build. The higher index of the pair goes first.
+    pub fn pairs(&self) -> &[(usize, usize)] {
+        if self.valid { &self.pairs } else { &[] }
+    }
+
+    /// Forces a rebuild before the next use, for example when the indices of the bodies change.
+    pub fn invalidate(&mut self) {
+        self.valid = false;
+    }
+
+    /// Checks if the list must be rebuilt for the current state of the bodies:
+    /// some body moved more than half the skin since the last build (through the nearest image),
+    /// or changed its shape or its mass.
+    pub fn needs_rebuild<T: Topology<D>>(&self, topology: &T, bodies: &[Body<D>]) -> bool {
+        if !self.valid || self.radii.len() != bodies.len() {
+            return true
+        }
+        let max_displacement = self.skin / 2.0;
+        bodies.iter().enumerate().any(|(index, body)| {
+            let (displacement, _) = topology.displacement(body.position(), self.positions[index]);
+            radius(body) != self.radii[index]
+                || body.inverse_mass() != self.inverse_masses[index]
+                || displacement.square_length() > max_displacement * max_displacement
+        })
+    }
+
+    /// Keeps the candidate pairs of the broad phase that are closer than the skin,
+    /// and stores the state of the bodies to check when the list expires.
+    /// The broad phase must have received the regions of the bodies enlarged by half the skin.
+    pub fn rebuild<T: Topology<D>>(&mut self, topology: &T, bodies: &[Body<D>], candidates: &[(usize, usize)]) {
+        let skin = self.skin;
+        self.pairs.clear();
+        self.pairs.extend(candidates.iter().filter(|&&(first, second)| {
+            let (b1, b2) = (&bodies[first], &bodies[second]);
+            let (displacement, _) = topology.displacement(b1.position(), b2.position());
+            let reach = radius(b1) + radius(b2) + skin;
+            displacement.square_length() < reach * reach
+        }));
+        self.positions.clear();
+        self.positions.extend(bodies.iter().map(|body| body.position()));
+        self.inverse_masses.clear();
+        self.inverse_masses.extend(bodies.iter().map(|body| body.inverse_mass()));
+        self.radii.clear();
+        self.radii.extend(bodies.iter().map(radius));
+        self.valid = true;
+    }
+}
+
+/// Zero for the bodies without shape.
+fn radius<const D: usize>(body: &Body<D>) -> Real {
+    match body.shape() {
+        Some(Shape::Circle(radius)) => *radius,
+        None => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::math::{Vec2, toroidal::Bounds};
+
+    #[test]
+    fn rebuild_after_moving_half_the_skin_across_the_edge() {
+        let bounds = Bounds::new(20.0, 10.0);
+        let mut bodies = Vec::new();
+        for &x in &[1.0, 18.0, 10.0] {
+            let mut body = Body::new(Vec2::xy(x, 5.0));
+            body.set_shape(Shape::Circle(1.0));
+            bodies.push(body);
+        }
+
+        let mut list = NeighborList::new(2.0);
+        assert!(list.needs_rebuild(&bounds, &bodies));
+        list.rebuild(&bounds, &bodies, &[(1, 0), (2, 0), (2, 1)]);
+        assert_eq!(list.pairs(), &[(1, 0)]); // 3 units apart through the edge
+        assert!(!list.needs_rebuild(&bounds, &bodies));
+
+        bodies[0].set_position(Vec2::xy(19.5, 5.0)); // 1.5 units through the edge
+        assert!(list.needs_rebuild(&bounds, &bodies));
+
+        bodies[0].set_position(Vec2::xy(0.5, 5.0));
+        assert!(!list.needs_rebuild(&bounds, &bodies));
+
+        bodies[2].set_shape(Shape::Circle(2.0));
+        assert!(list.needs_rebuild(&bounds, &bodies));
+    }
+}
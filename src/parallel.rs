@@ -0,0 +1,123 @@
+//! Support for the `parallel` feature.
+//! Without the feature only the [`Shareable`] bound exists, and it is satisfied by any type.
+
+/// Bound of the user types that the space shares among threads: force fields and topologies.
+/// With the `parallel` feature it means `Send + Sync`. Without it, any type is `Shareable`.
+#[cfg(feature = "parallel")]
+pub trait Shareable: Send + Sync {}
+
+#[cfg(feature = "parallel")]
+impl<T: Send + Sync> Shareable for T {}
+
+/// Bound of the user types that the space shares among threads: force fields and topologies.
+/// With the `parallel` feature it means `Send + Sync`. Without it, any type is `Shareable`.
+#[cfg(not(feature = "parallel"))]
+pub trait Shareable {}
+
+#[cfg(not(feature = "parallel"))]
+impl<T> Shareable for T {}
+
+#[cfg(feature = "parallel")]
+pub(crate) use self::pairs::for_each_pair_mut;
+
+#[cfg(feature = "parallel")]
+mod pairs {
+    use rayon::prelude::*;
+
+    /// Raw access to the elements of a slice from several threads.
+    /// Only sound while every thread touches different elements.
+    struct SharedSlice<T>(*mut T);
+
+    unsafe impl<T: Send> Sync for SharedSlice<T> {}
+
+    impl<T> SharedSlice<T> {
+        /// # Safety
+        /// The index must be in bounds and no other reference to the element may be alive.
+        unsafe fn get_mut<'a>(&self, index: usize) -> &'a mut T {
+            &mut *self.0.add(index)
+        }
+    }
+
+    /// Splits the pairs into batches of pairs that do not share any element.
+    /// Every pair goes to the batch after the last one that uses any of its elements,
+    /// so the pairs of each element keep their relative order.
+    fn disjoint_batches(pairs: &[(usize, usize)], element_count: usize) -> Vec<Vec<usize>> {
+        let mut next_batch = vec![0; element_count];
+        let mut batches: Vec<Vec<usize>> = Vec::new();
+        for (index, &(first, second)) in pairs.iter().enumerate() {
+            let batch = next_batch[first].max(next_batch[second]);
+            if batch == batches.len() {
+                batches.push(Vec::new());
+            }
+            batches[batch].push(index);
+            next_batch[first] = batch + 1;
+            next_batch[second] = batch + 1;
+        }
+        batches
+    }
+
+    /// Calls `action` for every pair with mutable access to both elements, running in parallel
+    /// the pairs that do not share elements. The result is the same as calling it sequentially
+    /// in the order of the pairs, whatever the number of threads.
+    /// The values returned by `action` are given in the order of the pairs.
+    pub fn for_each_pair_mut<T, R, F>(elements: &mut [T], pairs: &[(usize, usize)], action: F) -> Vec<R>
+    where T: Send, R: Send, F: Fn(usize, &mut T, &mut T) -> R + Sync {
+        for &(first, second) in pairs {
+            assert!(first != second && first < elements.len() && second < elements.len());
+        }
+
+        let shared = SharedSlice(elements.as_mut_ptr());
+        let mut results: Vec<Option<R>> = (0..pairs.len()).map(|_| None).collect();
+        for batch in disjoint_batches(pairs, elements.len()) {
+            let batch_results: Vec<(usize, R)> = batch.par_iter().map(|&index| {
+                let (first, second) = pairs[index];
+                // Safety: indices were checked above, and the pairs of a batch do not share elements.
+                let (a, b) = unsafe { (shared.get_mut(first), shared.get_mut(second)) };
+                (index, action(index, a, b))
+            }).collect();
+            for (index, result) in batch_results {
+                results[index] = Some(result);
+            }
+        }
+        results.into_iter().map(|result| result.unwrap()).collect()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn batches_keep_the_order_of_each_element() {
+            let pairs = [(0, 1), (2, 3), (1, 2), (0, 3), (4, 5)];
+            let batches = disjoint_batches(&pairs, 6);
+            assert_eq!(batches, vec![vec![0, 1, 4], vec![2, 3]]);
+        }
+
+        #[test]
+        fn same_result_as_sequential() {
+            let pairs: Vec<(usize, usize)> = (0..200).map(|i| ((i * 7) % 31, (i * 13 + 5) % 31))
+                .filter(|(a, b)| a != b)
+                .collect();
+
+            let mut sequential: Vec<f64> = (0..31).map(|i| i as f64).collect();
+            let mut expected = Vec::new();
+            for &(first, second) in &pairs {
+                let mean = (sequential[first] + sequential[second]) * 0.5 + 0.1;
+                sequential[first] = mean;
+                sequential[second] = mean * 1.01;
+                expected.push(mean);
+            }
+
+            let mut parallel: Vec<f64> = (0..31).map(|i| i as f64).collect();
+            let results = for_each_pair_mut(&mut parallel, &pairs, |_, a, b| {
+                let mean = (*a + *b) * 0.5 + 0.1;
+                *a = mean;
+                *b = mean * 1.01;
+                mean
+            });
+
+            assert_eq!(parallel, sequential);
+            assert_eq!(results, expected);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use super::math::{Vector, Real, consts, functions::{ln, sin, cos}};
+
+/// Small seeded pseudo random generator (xorshift64*).
+/// It is not cryptographically secure, but it is fast and fully reproducible for the same seed.
+pub struct Random {
+    state: u64,
+    spare_normal: Option<Real>,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        // The seed is scrambled with splitmix64 because xorshift can not start from a zero state.
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        Random { state: if z == 0 { 0x9E37_79B9_7F4A_7C15 } else { z }, spare_normal: None }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform value in the range [0, 1).
+    pub fn uniform(&mut self) -> Real {
+        (self.next_u64() >> 40) as Real / (1u64 << 24) as Real
+    }
+
+    /// Value from a normal distribution with mean 0 and standard deviation 1 (Box-Muller).
+    pub fn normal(&mut self) -> Real {
+        if let Some(value) = self.spare_normal.take() {
+            return value
+        }
+
+        let radius = (-2.0 * ln(1.0 - self.uniform())).sqrt();
+        let angle = 2.0 * consts::PI * self.uniform();
+        self.spare_normal = Some(radius * sin(angle));
+        radius * cos(angle)
+    }
+
+    /// Vector whose components are independent standard normal values.
+    pub fn normal_vector<const D: usize>(&mut self) -> Vector<D> {
+        Vector::from_fn(|_| self.normal())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut r1 = Random::new(42);
+        let mut r2 = Random::new(42);
+        for _ in 0..100 {
+            assert_eq!(r1.next_u64(), r2.next_u64());
+        }
+    }
+
+    #[test]
+    fn uniform_range() {
+        let mut random = Random::new(0);
+        for _ in 0..1000 {
+            let value = random.uniform();
+            assert!((0.0..1.0).contains(&value));
+        }
+    }
+
+    #[test]
+    fn normal_moments() {
+        let mut random = Random::new(7);
+        let samples = 20000;
+        let (mut sum, mut square_sum) = (0.0, 0.0);
+        for _ in 0..samples {
+            let value = random.normal();
+            sum += value;
+            square_sum += value * value;
+        }
+        let mean = sum / samples as Real;
+        let variance = square_sum / samples as Real - mean * mean;
+        assert!(mean.abs() < 0.05);
+        assert!((variance - 1.0).abs() < 0.05);
+    }
+}
@@ -0,0 +1,131 @@
+use super::math::{Vector, Real};
+
+/// Bits used to quantize each coordinate before computing the position along the curve.
+const BITS: u32 = 16;
+
+/// Curves that visit every cell of a grid passing close cells close in time.
+/// Sorting the bodies by their position along the curve keeps the bodies that are close in space
+/// also close in memory. See [`crate::Space::with_body_reordering()`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpaceFillingCurve {
+    /// Z-order curve: the bits of the coordinates interleaved. Cheap to compute,
+    /// but it jumps between distant cells at the edges of each quadrant.
+    Morton,
+
+    /// Hilbert curve: consecutive cells are always adjacent, so the locality is better than
+    /// with the Morton curve at a slightly higher computation cost.
+    Hilbert,
+}
+
+impl SpaceFillingCurve {
+    /// Position along the curve of a point of a space of the given dimension.
+    /// The coordinates are quantized in 2^16 cells per axis. Points out of the space are clamped.
+    pub fn key<const D: usize>(&self, position: Vector<D>, dimension: Vector<D>) -> u64 {
+        let max_cell = (1u32 << BITS) - 1;
+        let mut cell = [0; D];
+        for (axis, coordinate) in cell.iter_mut().enumerate() {
+            let scaled = position[axis] / dimension[axis] * (1u32 << BITS) as Real;
+            *coordinate = (scaled as u32).min(max_cell); // negative values saturate to 0
+        }
+        self.cell_key(cell, BITS)
+    }
+
+    /// Position along the curve of a cell of a grid with 2^bits cells per axis.
+    fn cell_key<const D: usize>(&self, mut cell: [u32; D], bits: u32) -> u64 {
+        if let SpaceFillingCurve::Hilbert = self {
+            hilbert_transpose(&mut cell, bits);
+        }
+        interleave(&cell, bits)
+    }
+}
+
+/// Joins the bits of the coordinates from the most significant one, the first axis going first.
+fn interleave<const D: usize>(cell: &[u32; D], bits: u32) -> u64 {
+    let mut key = 0;
+    for bit in (0..bits).rev() {
+        for coordinate in cell {
+            key = key << 1 | ((coordinate >> bit) & 1) as u64;
+        }
+    }
+    key
+}
+
+/// Converts the coordinates of a cell into the transposed Hilbert index (J. Skilling, 2004):
+/// interleaving the bits of the result gives the position of the cell along the Hilbert curve.
+fn hilbert_transpose<const D: usize>(cell: &mut [u32; D], bits: u32) {
+    if bits == 0 {
+        return
+    }
+    let highest = 1 << (bits - 1);
+
+    // Inverse undo of the rotations and reflections.
+    let mut q = highest;
+    while q > 1 {
+        let p = q - 1;
+        for axis in 0..D {
+            if cell[axis] & q != 0 {
+                cell[0] ^= p;
+            }
+            else {
+                let t = (cell[0] ^ cell[axis]) & p;
+                cell[0] ^= t;
+                cell[axis] ^= t;
+            }
+        }
+        q >>= 1;
+    }
+
+    // Gray encoding.
+    for axis in 1..D {
+        cell[axis] ^= cell[axis - 1];
+    }
+    let mut t = 0;
+    let mut q = highest;
+    while q > 1 {
+        if cell[D - 1] & q != 0 {
+            t ^= q - 1;
+        }
+        q >>= 1;
+    }
+    for coordinate in cell.iter_mut() {
+        *coordinate ^= t;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cells of a grid with 2^bits cells per axis, sorted along the curve.
+    fn sorted_cells<const D: usize>(curve: SpaceFillingCurve, bits: u32) -> Vec<[u32; D]> {
+        let side = 1u32 << bits;
+        let mut cells: Vec<[u32; D]> = (0..side.pow(D as u32)).map(|index| {
+            let mut cell = [0; D];
+            for (axis, coordinate) in cell.iter_mut().enumerate() {
+                *coordinate = index / side.pow(axis as u32) % side;
+            }
+            cell
+        }).collect();
+        cells.sort_by_key(|cell| curve.cell_key(*cell, bits));
+        cells
+    }
+
+    fn steps<const D: usize>(cells: &[[u32; D]]) -> Vec<u32> {
+        cells.windows(2).map(|pair| (0..D).map(|axis| pair[0][axis].abs_diff(pair[1][axis])).sum()).collect()
+    }
+
+    #[test]
+    fn morton_interleaves_the_bits() {
+        let curve = SpaceFillingCurve::Morton;
+        assert_eq!(sorted_cells::<2>(curve, 1), vec![[0, 0], [0, 1], [1, 0], [1, 1]]);
+        assert_eq!(curve.cell_key([0b10, 0b11], 2), 0b1101);
+    }
+
+    #[test]
+    fn hilbert_moves_between_adjacent_cells() {
+        let curve = SpaceFillingCurve::Hilbert;
+        assert!(steps(&sorted_cells::<2>(curve, 4)).iter().all(|step| *step == 1));
+        assert!(steps(&sorted_cells::<3>(curve, 3)).iter().all(|step| *step == 1));
+        assert!(steps(&sorted_cells::<2>(SpaceFillingCurve::Morton, 4)).iter().any(|step| *step > 1));
+    }
+}
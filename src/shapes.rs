@@ -1,63 +1,92 @@
-use super::math::{Vec2, toroidal::{Bounds}};
+use super::math::{Vector, Real, topology::{Topology, Orientation, Image}};
 
+/// Shape of a body. The same shape is used by the spaces of any dimension.
 #[derive(Clone)]
 pub enum Shape {
-    Circle(f32),
+    /// Ball of the given radius: an interval in 1D, a circle in 2D and a sphere in 3D.
+    Circle(Real),
 }
 
 impl Shape {
-    pub fn half_dimension(&self) -> Vec2 {
+    pub fn half_dimension<const D: usize>(&self) -> Vector<D> {
         match *self {
-            Shape::Circle(radius) => Vec2::xy(radius, radius)
+            Shape::Circle(radius) => Vector::splat(radius)
         }
     }
 }
 
-pub struct Contact {
-    normal: Vec2,
-    overlap: f32,
+pub struct Contact<const D: usize = 2> {
+    normal: Vector<D>,
+    overlap: Real,
+    distance: Real,
+    orientation: Orientation<D>,
+    velocity_shift: Vector<D>,
 }
 
-impl Contact {
-    pub fn normal(&self) -> Vec2 {
+impl<const D: usize> Contact<D> {
+    pub fn normal(&self) -> Vector<D> {
         self.normal
     }
 
-    pub fn overlap(&self) -> f32 {
+    pub fn overlap(&self) -> Real {
         self.overlap
     }
+
+    /// Distance between the position of the first body and the image of the second body involved in the contact.
+    pub fn distance(&self) -> Real {
+        self.distance
+    }
+
+    /// Transformation between the directions of the second body and the directions of the contact.
+    /// It is not the identity when the contact happens across a seam glued with a flip.
+    pub fn orientation(&self) -> Orientation<D> {
+        self.orientation
+    }
+
+    /// Velocity of the image of the second body involved in the contact relative to the second body.
+    /// It is not zero when the contact happens across a sheared edge.
+    pub fn velocity_shift(&self) -> Vector<D> {
+        self.velocity_shift
+    }
 }
 
 
-pub struct CollisionResolver<'a> {
-    bounds: &'a Bounds,
+/// Finds the contacts between the shapes of the bodies, through all their periodic images.
+pub struct CollisionResolver<'a, T: Topology<D>, const D: usize = 2> {
+    topology: &'a T,
+    images: Vec<Image<D>>, // stored for performance
 }
 
-impl<'a> CollisionResolver<'a> {
-    pub fn new(bounds: &'a Bounds) -> CollisionResolver {
-        CollisionResolver { bounds }
+impl<'a, T: Topology<D>, const D: usize> CollisionResolver<'a, T, D> {
+    pub fn new(topology: &'a T) -> CollisionResolver<'a, T, D> {
+        CollisionResolver { topology, images: Vec::new() }
     }
 
-    pub fn check_collision(&self, p1: Vec2, s1: &Shape, p2: Vec2, s2: &Shape) -> Option<Contact> {
+    /// Pushes one contact for each image of the second shape that overlaps the first shape.
+    /// Two shapes can touch through several images in small spaces or if they are larger than half the space.
+    pub fn check_collisions(&mut self, p1: Vector<D>, s1: &Shape, p2: Vector<D>, s2: &Shape, output: &mut Vec<Contact<D>>) {
         match *s1 {
             Shape::Circle(r1) => match *s2 {
                 Shape::Circle(r2) => {
-                    self.check_circle_circle(p1, r1, p2, r2)
+                    self.check_circle_circle(p1, r1, p2, r2, output)
                 },
             },
         }
     }
 
-    fn check_circle_circle(&self, p1: Vec2, r1: f32, p2: Vec2, r2: f32) -> Option<Contact> {
-        let distance = self.bounds.get_toroidal_distance(p1 - p2);
+    fn check_circle_circle(&mut self, p1: Vector<D>, r1: Real, p2: Vector<D>, r2: Real, output: &mut Vec<Contact<D>>) {
         let collision_length = r1 + r2;
-        if distance.square_length() < collision_length * collision_length {
-            let length = distance.square_length().sqrt();
-            Some(Contact {
-                normal: if length > std::f32::EPSILON { distance / length } else { Vec2::zero() },
+        self.images.clear();
+        self.topology.images(p1, p2, collision_length, &mut self.images);
+        for image in &self.images {
+            let length = image.displacement.length();
+            output.push(Contact {
+                normal: if length > Real::EPSILON { image.displacement / length } else { Vector::zero() },
                 overlap: collision_length - length,
-            })
+                distance: length,
+                orientation: image.orientation,
+                velocity_shift: image.velocity_shift,
+            });
         }
-        else { None }
     }
 }
@@ -1,52 +1,371 @@
-use super::math::toroidal::{Bounds};
+use super::math::{Vector, Real, bounding::{AxisAlignmentBoundingBox}, toroidal::{Bounds}, topology::{Topology, Confinement}, functions::powf};
 use super::body::{Body, Particle, ContactResolver};
 use super::shapes::{CollisionResolver, Contact};
-use super::spatial_table::{SpatialTable};
+use super::broad_phase::{BroadPhase, SpatialTable};
+use super::neighbor_list::{NeighborList};
+use super::integrator::{Integrator, ForceField};
+use super::thermostat::{Thermostat};
+use super::barostat::{Barostat};
+use super::events::{SeamCrossing, Axis, Direction};
+use super::random::{Random};
+use super::statistics::{Statistics};
+use super::reordering::{SpaceFillingCurve};
+#[cfg(not(feature = "parallel"))]
 use super::util::{BorrowMutTwo};
 
-use std::time::Duration;
+#[cfg(feature = "parallel")]
+use super::parallel::for_each_pair_mut;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use std::time::{Duration, Instant};
 use std::slice::{Iter, IterMut};
 
-pub struct Space {
-    bounds: Bounds,
-    bodies: Vec<Body>,
-    spatial_table: SpatialTable,
-    contacts_info: Vec<ContactInfo>, // stored for performance
+/// Number of candidate pairs checked by each task of the parallel narrow phase.
+#[cfg(feature = "parallel")]
+const CONTACT_BATCH_SIZE: usize = 256;
+
+/// Stable identifier of a body added to a [`Space`].
+/// It keeps identifying the same body when other bodies are removed from the space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BodyHandle(usize);
+
+impl BodyHandle {
+    /// Unique number of the handle inside its space. Handles are numbered in order of addition.
+    pub fn id(&self) -> usize {
+        self.0
+    }
+}
+
+/// The simulated space of `D` dimensions.
+/// By default it is a 2D torus ([`Bounds`]), but any other [`Topology`] can be used.
+/// Rings (1D) and 3D toroidal volumes are built with [`Space::ring()`] and [`Space::volume()`].
+/// Spaces of more than three dimensions are not supported.
+pub struct Space<T: Topology<D> = Bounds, const D: usize = 2> {
+    bounds: T,
+    bodies: Vec<Body<D>>,
+    handles: Vec<BodyHandle>,
+    indices: Vec<Option<usize>>, // body index of each handle id
+    absorbed: Vec<(BodyHandle, Body<D>)>,
+    seam_crossings: Vec<SeamCrossing<D>>,
+    broad_phase: Box<dyn BroadPhase<D>>,
+    cell_size_tuning: Option<CellSizeTuning>,
+    reordering: Option<Reordering>,
+    neighbor_list: Option<NeighborList<D>>,
+    statistics: Statistics,
+    aabbs: Vec<AxisAlignmentBoundingBox<D>>, // stored for performance
+    candidates: Vec<(usize, usize)>, // stored for performance
+    #[cfg(not(feature = "parallel"))]
+    contacts: Vec<Contact<D>>, // stored for performance
+    contacts_info: Vec<ContactInfo<D>>, // stored for performance
+    integrator: Integrator,
+    force_fields: Vec<Box<dyn ForceField<D>>>,
+    thermostat: Option<Thermostat>,
+    random: Random,
+    adaptive_substeps: Option<AdaptiveSubsteps>,
+    last_substeps: u32,
+    barostat: Option<Barostat>,
+    virial: Real,
+    pressure: Real,
 }
 
 impl Space {
-    pub fn new(width: u32, height: u32) -> Space {
+    pub fn new(width: Real, height: Real) -> Space {
+        Space::with_topology(Bounds::new(width, height))
+    }
+}
+
+impl Space<Bounds<1>, 1> {
+    /// Creates a 1D periodic space: a ring of the given length.
+    pub fn ring(length: Real) -> Space<Bounds<1>, 1> {
+        Space::with_topology(Bounds::ring(length))
+    }
+}
+
+impl Space<Bounds<3>, 3> {
+    /// Creates a 3D periodic space: a 3-torus built from a rectangular cuboid.
+    pub fn volume(width: Real, height: Real, depth: Real) -> Space<Bounds<3>, 3> {
+        Space::with_topology(Bounds::volume(width, height, depth))
+    }
+}
+
+impl<T: Topology<D>, const D: usize> Space<T, D> {
+    /// Creates a space whose edges are glued following the given topology.
+    pub fn with_topology(topology: T) -> Space<T, D> {
+        assert!((1..=3).contains(&D), "Only spaces of 1, 2 or 3 dimensions are supported");
+        let dimension = topology.dimension();
         Space {
-            bounds: Bounds::new(width, height),
+            bounds: topology,
             bodies: Vec::new(),
+            handles: Vec::new(),
+            indices: Vec::new(),
+            absorbed: Vec::new(),
+            seam_crossings: Vec::new(),
+            #[cfg(not(feature = "parallel"))]
+            contacts: Vec::new(),
             contacts_info: Vec::new(),
-            spatial_table: SpatialTable::new(width, height, width as f32 / 10.0),
+            broad_phase: Box::new(SpatialTable::new(dimension, dimension[0] / 10.0)),
+            cell_size_tuning: Some(CellSizeTuning { countdown: 0 }),
+            reordering: None,
+            neighbor_list: None,
+            statistics: Statistics::default(),
+            aabbs: Vec::new(),
+            candidates: Vec::new(),
+            integrator: Integrator::default(),
+            force_fields: Vec::new(),
+            thermostat: None,
+            random: Random::new(0),
+            adaptive_substeps: None,
+            last_substeps: 1,
+            barostat: None,
+            virial: 0.0,
+            pressure: 0.0,
         }
     }
 
     /// A convenient method to modify optionally the internal cell_size during the Space building.
     /// This cell_size value is used as a heuristic for performance reasons when collisions are involved.
     /// A value between 1 or 2 times the average size of the shapes works fine.
-    pub fn with_optimization_cell_size(mut self, cell_size: f32) -> Space {
-        self.spatial_table = SpatialTable::new(self.bounds.width, self.bounds.height, cell_size);
+    /// It replaces the broad phase by a [`SpatialTable`] with the given cell size.
+    /// By default, the space tunes the cell size from the sizes of the shapes of its bodies:
+    /// setting it explicitly disables the tuning.
+    pub fn with_optimization_cell_size(mut self, cell_size: Real) -> Space<T, D> {
+        self.broad_phase = Box::new(SpatialTable::new(self.bounds.dimension(), cell_size));
+        self.cell_size_tuning = None;
         self
     }
 
-    pub fn bounds(&self) -> &Bounds {
+    /// A convenient method to choose the broad phase of the collision detection during the Space building.
+    /// By default, a [`SpatialTable`] is used whose cell size the space tunes from the sizes of the shapes of its bodies.
+    /// Choosing the broad phase explicitly disables the tuning.
+    /// See [`crate::broad_phase`] for the available algorithms.
+    pub fn with_broad_phase<B: BroadPhase<D> + 'static>(mut self, mut broad_phase: B) -> Space<T, D> {
+        broad_phase.resize(self.bounds.dimension());
+        self.broad_phase = Box::new(broad_phase);
+        self.cell_size_tuning = None;
+        self
+    }
+
+    /// A convenient method to sort periodically the bodies along a space-filling curve during the Space building.
+    /// After many steps, the bodies close in space end scattered in memory; sorting them every
+    /// `interval` updates keeps the neighbors together, so the collision detection and resolution
+    /// access the memory in order. The order of [`Space::bodies()`] changes: use the handles to track the bodies.
+    pub fn with_body_reordering(mut self, curve: SpaceFillingCurve, interval: u32) -> Space<T, D> {
+        self.reordering = Some(Reordering { curve, interval: interval.max(1), countdown: 0 });
+        self
+    }
+
+    /// A convenient method to reuse the candidate pairs of the collision detection between steps during the Space building.
+    /// The broad phase is only asked for the pairs whose shapes are closer than the skin distance
+    /// (a Verlet neighbor list), and again once some body has moved more than half the skin
+    /// since then through its nearest image, or has changed its shape or mass.
+    /// A larger skin rebuilds the list less often, but the list is longer.
+    /// A shear of the space (see [`Bounds::with_lees_edwards()`]) moving the images faster than half
+    /// the skin between rebuilds can miss contacts across the sheared edges.
+    pub fn with_neighbor_lists(mut self, skin: Real) -> Space<T, D> {
+        self.neighbor_list = Some(NeighborList::new(skin));
+        self
+    }
+
+    /// Pairs of bodies whose shapes were closer than the skin of the neighbor list when it was last built,
+    /// to evaluate short-range interactions between the bodies (at most up to the skin distance beyond contact).
+    /// It is empty if the neighbor lists are not enabled, or after adding or removing bodies until the next update.
+    pub fn neighbor_pairs(&self) -> impl Iterator<Item = (BodyHandle, BodyHandle)> + '_ {
+        let pairs = self.neighbor_list.as_ref().map_or(&[][..], |list| list.pairs());
+        pairs.iter().map(move |&(first, second)| (self.handles[first], self.handles[second]))
+    }
+
+    /// Measures of the collision detection during the last update.
+    pub fn statistics(&self) -> Statistics {
+        Statistics {
+            cell_size: self.broad_phase.cell_size(),
+            occupancy: self.broad_phase.occupancy(),
+            ..self.statistics
+        }
+    }
+
+    /// A convenient method to choose the integration scheme during the Space building.
+    /// By default, [`Integrator::SemiImplicitEuler`] is used.
+    pub fn with_integrator(mut self, integrator: Integrator) -> Space<T, D> {
+        self.integrator = integrator;
+        self
+    }
+
+    pub fn integrator(&self) -> Integrator {
+        self.integrator
+    }
+
+    /// Adds a force field that is evaluated at every stage of the integrator.
+    /// Use it instead of [`Body::add_force()`] for forces that depend on the position or the velocity,
+    /// so multi-stage integrators can take the change of the force during the step into account.
+    pub fn add_force_field<F: ForceField<D> + 'static>(&mut self, field: F) {
+        self.force_fields.push(Box::new(field));
+    }
+
+    /// A convenient method to couple the bodies to a heat bath during the Space building.
+    /// The seed feeds the internal random generator used for the thermal noise,
+    /// so two spaces built with the same seed and bodies evolve identically.
+    pub fn with_thermostat(mut self, thermostat: Thermostat, seed: u64) -> Space<T, D> {
+        self.thermostat = Some(thermostat);
+        self.random = Random::new(seed);
+        self
+    }
+
+    pub fn thermostat(&self) -> Option<&Thermostat> {
+        self.thermostat.as_ref()
+    }
+
+    /// A convenient method to split each update into substeps during the Space building.
+    /// The number of substeps is chosen so that no body moves more than `max_fraction` of its shape size
+    /// (or of the optimization cell size, if it is smaller) in one substep, with a limit of `max_substeps`.
+    /// The displacement is estimated from the velocities of the bodies at the beginning of the update.
+    pub fn with_adaptive_substeps(mut self, max_fraction: Real, max_substeps: u32) -> Space<T, D> {
+        self.adaptive_substeps = Some(AdaptiveSubsteps { max_fraction, max_substeps: max_substeps.max(1) });
+        self
+    }
+
+    /// Number of substeps used by the last call to [`Space::update()`].
+    /// It is always 1 if the adaptive substeps are not enabled.
+    pub fn last_substeps(&self) -> u32 {
+        self.last_substeps
+    }
+
+    /// A convenient method to control the pressure during the Space building.
+    /// After each update, the space is resized to relax the measured pressure towards the target.
+    pub fn with_barostat(mut self, barostat: Barostat) -> Space<T, D> {
+        self.barostat = Some(barostat);
+        self
+    }
+
+    pub fn barostat(&self) -> Option<&Barostat> {
+        self.barostat.as_ref()
+    }
+
+    /// Changes the dimension of the space during the simulation.
+    /// The positions of the bodies are rescaled by the same factor in each axis (affine rescaling)
+    /// and the broad phase is rebuilt for the new dimension (a [`SpatialTable`] keeps its cell size).
+    /// The velocities are not modified.
+    /// In skewed cells, only isotropic rescaling keeps the bodies in the same place relative to the cell.
+    pub fn resize(&mut self, dimension: Vector<D>) {
+        let old_dimension = self.bounds.dimension();
+        self.bounds.resize(dimension);
+        self.broad_phase.resize(dimension);
+        self.invalidate_neighbor_list();
+        for body in &mut self.bodies {
+            let position = body.position().map(|axis, coordinate| coordinate * dimension[axis] / old_dimension[axis]);
+            body.set_position(self.bounds.normalize(position).0);
+        }
+    }
+
+    /// Pressure measured during the last update with the virial expression:
+    /// the kinetic contribution of the bodies of finite mass plus the impulses exchanged in the collisions,
+    /// divided by `D` times the volume (the area in 2D) of the space.
+    pub fn pressure(&self) -> Real {
+        self.pressure
+    }
+
+    /// The topology of the space. For the default toroidal space, its [`Bounds`].
+    pub fn bounds(&self) -> &T {
         &self.bounds
     }
 
-    pub fn bodies(&self) -> Iter<Body> {
+    pub fn bodies(&self) -> Iter<Body<D>> {
         self.bodies.iter()
     }
 
-    pub fn bodies_mut(&mut self) -> IterMut<Body> {
+    pub fn bodies_mut(&mut self) -> IterMut<Body<D>> {
         self.bodies.iter_mut()
     }
 
-    pub fn add(&mut self, mut body: Body) {
-        body.set_position(self.bounds.get_toroidal_position(body.position()));
+    /// Handles of the bodies, in the same order as [`Space::bodies()`].
+    pub fn handles(&self) -> Iter<'_, BodyHandle> {
+        self.handles.iter()
+    }
+
+    pub fn body(&self, handle: BodyHandle) -> Option<&Body<D>> {
+        let index = (*self.indices.get(handle.0)?)?;
+        Some(&self.bodies[index])
+    }
+
+    pub fn body_mut(&mut self, handle: BodyHandle) -> Option<&mut Body<D>> {
+        let index = (*self.indices.get(handle.0)?)?;
+        Some(&mut self.bodies[index])
+    }
+
+    pub fn add(&mut self, mut body: Body<D>) -> BodyHandle {
+        body.set_position(self.bounds.normalize(body.position()).0);
+        let handle = BodyHandle(self.indices.len());
+        self.indices.push(Some(self.bodies.len()));
+        self.handles.push(handle);
         self.bodies.push(body);
+        self.invalidate_neighbor_list();
+        handle
+    }
+
+    /// Position of the body without wrapping it around the edges of the space:
+    /// its position moved by the translation of the space for its winding (see [`Topology::translation()`]).
+    pub fn unwrapped_position(&self, handle: BodyHandle) -> Option<Vector<D>> {
+        let body = self.body(handle)?;
+        Some(body.position() + self.bounds.translation(body.winding()))
+    }
+
+    /// Removes a body from the space, keeping the order of the rest of bodies.
+    pub fn remove(&mut self, handle: BodyHandle) -> Option<Body<D>> {
+        let index = (*self.indices.get(handle.0)?)?;
+        self.remove_indices(&[index]).pop().map(|(_, body)| body)
+    }
+
+    /// Bodies removed by the absorbing edges of the space during the last update.
+    pub fn absorbed(&self) -> Iter<'_, (BodyHandle, Body<D>)> {
+        self.absorbed.iter()
+    }
+
+    /// Bodies that wrapped around the glued edges of the space during the last update.
+    /// A body turning several times around the same edges in one update produces one event per turn.
+    pub fn seam_crossings(&self) -> Iter<'_, SeamCrossing<D>> {
+        self.seam_crossings.iter()
+    }
+
+    /// Removes the bodies of the sorted indices, returning them with their handles.
+    fn remove_indices(&mut self, removed: &[usize]) -> Vec<(BodyHandle, Body<D>)> {
+        self.broad_phase.clear(); // The indices of the bodies change
+        self.invalidate_neighbor_list();
+        let bodies = std::mem::take(&mut self.bodies);
+        let handles = std::mem::take(&mut self.handles);
+        let mut removed = removed.iter().peekable();
+        let mut output = Vec::new();
+        for (index, (handle, body)) in handles.into_iter().zip(bodies).enumerate() {
+            if removed.peek() == Some(&&index) {
+                removed.next();
+                self.indices[handle.0] = None;
+                output.push((handle, body));
+            }
+            else {
+                self.indices[handle.0] = Some(self.bodies.len());
+                self.handles.push(handle);
+                self.bodies.push(body);
+            }
+        }
+        output
+    }
+
+    fn invalidate_neighbor_list(&mut self) {
+        if let Some(list) = &mut self.neighbor_list {
+            list.invalidate();
+        }
+    }
+
+    /// Temperature measured from the kinetic energy of the bodies (the Boltzmann constant is taken as 1).
+    /// In a space of `D` dimensions each body has `D` degrees of freedom.
+    /// The bodies of infinite mass (static walls) have no degrees of freedom and are not counted.
+    pub fn kinetic_temperature(&self) -> Real {
+        let (count, kinetic_energy) = self.bodies.iter()
+            .filter(|body| body.mass().is_finite())
+            .fold((0, 0.0), |(count, energy), body| (count + 1, energy + body.velocity().square_length() * body.mass() / 2.0));
+        if count == 0 {
+            return 0.0
+        }
+        2.0 * kinetic_energy / (D * count) as Real
     }
 
     /// Main function that performs a physics step over the bodies in the space.
@@ -54,47 +373,862 @@ impl Space {
     /// It represents the physics interval that will be emulated.
     /// High 'duration' values will need less calls to this function but will reduce the physics accuracy resolution.
     /// Less 'duration' values will need more calls to update but will improve the physics accuracy resolution.
+    /// See [`Space::with_adaptive_substeps()`] to let the space split the duration automatically.
+    /// The forces added to the bodies are applied during the whole duration and cleared afterwards.
     pub fn update(&mut self, duration: Duration) {
-        let dt = duration.as_secs_f32();
+        let dt = duration.as_secs_f64() as Real;
+        self.reorder_bodies();
+        self.tune_cell_size();
+        self.statistics = Statistics::default();
+        let substeps = match &self.adaptive_substeps {
+            Some(adaptive_substeps) => adaptive_substeps.compute(&self.bodies, self.broad_phase.cell_size(), dt),
+            None => 1,
+        };
+
+        self.virial = 0.0;
+        self.absorbed.clear();
+        self.seam_crossings.clear();
+        for _ in 0..substeps {
+            self.step(dt / substeps as Real);
+        }
+
         for body in &mut self.bodies {
-            body.integrate(dt);
+            body.set_force(Vector::zero());
         }
+        self.last_substeps = substeps;
 
-        self.spatial_table.clear();
-        for (i, body) in self.bodies.iter().enumerate() {
-            if let Some(aabb) = body.aabb() {
-                self.spatial_table.insert(i, &self.bounds.get_toroidal_aabb(&aabb));
+        let kinetic: Real = self.bodies.iter()
+            .filter(|body| body.mass().is_finite())
+            .map(|body| body.velocity().square_length() * body.mass())
+            .sum();
+        let collisions = if dt > 0.0 { self.virial / dt } else { 0.0 };
+        self.pressure = (kinetic + collisions) / (D as Real * self.bounds.cell_volume());
+
+        if let Some(barostat) = &self.barostat {
+            self.resize(self.bounds.dimension() * barostat.length_scale::<D>(self.pressure, dt));
+        }
+    }
+
+    /// Sorts the bodies along the curve of the reordering, if it is its turn.
+    fn reorder_bodies(&mut self) {
+        let reordering = match &mut self.reordering {
+            Some(reordering) => reordering,
+            None => return,
+        };
+        if reordering.countdown > 0 {
+            reordering.countdown -= 1;
+            return
+        }
+        reordering.countdown = reordering.interval - 1;
+
+        let (curve, dimension) = (reordering.curve, self.bounds.dimension());
+        let keys: Vec<_> = self.bodies.iter().enumerate().map(|(index, body)| (curve.key(body.position(), dimension), index)).collect();
+        if keys.windows(2).all(|pair| pair[0] <= pair[1]) {
+            return
+        }
+
+        self.broad_phase.clear(); // The indices of the bodies change
+        self.invalidate_neighbor_list();
+        let bodies = std::mem::take(&mut self.bodies);
+        let handles = std::mem::take(&mut self.handles);
+        let mut entries: Vec<_> = keys.into_iter().zip(handles.into_iter().zip(bodies)).collect();
+        entries.sort_unstable_by_key(|(key, _)| *key);
+        for (_, (handle, body)) in entries {
+            self.indices[handle.0] = Some(self.bodies.len());
+            self.handles.push(handle);
+            self.bodies.push(body);
+        }
+    }
+
+    /// Rebuilds the default grid when the cell size that fits the shapes of the bodies changes noticeably.
+    fn tune_cell_size(&mut self) {
+        let tuning = match &mut self.cell_size_tuning {
+            Some(tuning) => tuning,
+            None => return,
+        };
+        if tuning.countdown > 0 {
+            tuning.countdown -= 1;
+            return
+        }
+        tuning.countdown = CellSizeTuning::INTERVAL;
+
+        let diameters = self.bodies.iter().filter_map(|body| body.shape()).map(|shape| shape.half_dimension::<D>().min_component() * 2.0);
+        let (count, total) = diameters.fold((0, 0.0), |(count, total), diameter| (count + 1, total + diameter));
+        if count == 0 {
+            return
+        }
+        let dimension = self.bounds.dimension();
+        let sparse_size = powf(dimension.product() / (CellSizeTuning::CELLS_PER_BODY * count as Real), 1.0 / D as Real);
+        let cell_size = (total / count as Real * CellSizeTuning::DIAMETER_FACTOR).max(sparse_size);
+
+        let current = self.broad_phase.cell_size().unwrap_or(Real::INFINITY);
+        if (cell_size - current).abs() > current * CellSizeTuning::TOLERANCE {
+            self.broad_phase = Box::new(SpatialTable::new(dimension, cell_size));
+        }
+    }
+
+    fn step(&mut self, dt: Real) {
+        let start = Instant::now();
+        match &self.thermostat {
+            #[cfg(not(feature = "parallel"))]
+            None => for body in &mut self.bodies {
+                body.integrate(dt, self.integrator, &self.force_fields);
+            },
+            #[cfg(feature = "parallel")]
+            None => {
+                let (integrator, force_fields) = (self.integrator, &self.force_fields);
+                self.bodies.par_iter_mut().for_each(|body| body.integrate(dt, integrator, force_fields));
+            },
+            // Sequential: the thermal noise is drawn from a single random sequence.
+            Some(thermostat) => for body in &mut self.bodies {
+                thermostat.integrate(body, dt, self.integrator, &self.force_fields, &mut self.random);
+            },
+        }
+        self.bounds.advance(dt);
+        self.statistics.integration_time += start.elapsed();
+
+        let start = Instant::now();
+        let rebuild = match &self.neighbor_list {
+            Some(list) => list.needs_rebuild(&self.bounds, &self.bodies),
+            None => true,
+        };
+        if rebuild {
+            let margin = self.neighbor_list.as_ref().map_or(0.0, |list| list.skin() / 2.0);
+            for (i, body) in self.bodies.iter().enumerate() {
+                self.aabbs.clear();
+                if let Some(aabb) = body.aabb() {
+                    let aabb = AxisAlignmentBoundingBox::new(aabb.position(), aabb.half_dimension() + Vector::splat(margin));
+                    self.bounds.wrapped_aabbs(&aabb, &mut self.aabbs);
+                }
+                self.broad_phase.update(i, &self.aabbs);
+            }
+        }
+
+        let pairs = match &mut self.neighbor_list {
+            Some(list) => {
+                if rebuild {
+                    list.rebuild(&self.bounds, &self.bodies, self.broad_phase.pairs());
+                    self.statistics.neighbor_list_builds += 1;
+                }
+                list.pairs()
+            },
+            None => self.broad_phase.pairs(),
+        };
+        self.statistics.broad_phase_pairs += pairs.len();
+        self.statistics.broad_phase_time += start.elapsed();
+
+        let start = Instant::now();
+        // Two bodies of infinite mass cannot move each other: their contacts are skipped.
+        let bodies = &self.bodies;
+        self.candidates.clear();
+        self.candidates.extend(pairs.iter().filter(|&&(first, second)| {
+            bodies[first].inverse_mass() + bodies[second].inverse_mass() > 0.0
+        }));
+        #[cfg(feature = "deterministic")]
+        self.candidates.sort_unstable(); // The contacts are resolved in the same order whatever the broad phase
+        self.find_contacts();
+        self.statistics.candidate_pairs += self.candidates.len();
+        self.statistics.contacts += self.contacts_info.len();
+        self.statistics.narrow_phase_time += start.elapsed();
+
+        let start = Instant::now();
+        self.resolve_contacts();
+        self.statistics.contact_resolution_time += start.elapsed();
+
+        let mut absorbed = Vec::new();
+        for (index, body) in self.bodies.iter_mut().enumerate() {
+            let position_before = body.position();
+            let half_extent = body.shape().map_or(Vector::zero(), |shape| shape.half_dimension());
+            match self.bounds.confine(position_before, half_extent) {
+                Confinement::Inside { position, orientation, reflection, velocity_shift, winding } => {
+                    let mut total_winding = body.winding();
+                    for (axis, turns) in winding.iter().enumerate() {
+                        let direction = if *turns > 0 { Direction::Positive } else { Direction::Negative };
+                        for _ in 0..turns.abs() {
+                            self.seam_crossings.push(SeamCrossing {
+                                body: self.handles[index],
+                                axis: Axis::from_index(axis),
+                                direction,
+                                position_before,
+                                position_after: position,
+                            });
+                        }
+                        total_winding[axis] += turns;
+                    }
+                    body.set_position(position);
+                    body.set_winding(total_winding);
+                    if !orientation.is_identity() {
+                        body.set_velocity(orientation.apply(body.velocity()));
+                        body.set_force(orientation.apply(body.force()));
+                    }
+                    body.add_velocity(velocity_shift);
+                    if !reflection.is_identity() {
+                        let restitution = body.restitution();
+                        body.set_velocity(reflection.apply(body.velocity()).map(|axis, component| {
+                            if reflection.flips[axis] { component * restitution } else { component }
+                        }));
+                    }
+                },
+                Confinement::Absorbed => absorbed.push(index),
             }
         }
 
-        let collision_resolver = CollisionResolver::new(&self.bounds);
+        if !absorbed.is_empty() {
+            let mut removed = self.remove_indices(&absorbed);
+            self.absorbed.append(&mut removed);
+        }
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    fn find_contacts(&mut self) {
+        let mut collision_resolver = CollisionResolver::new(&self.bounds);
         self.contacts_info.clear();
-        for pair in self.spatial_table.pairs() {
+        for pair in &self.candidates {
             let b1 = &self.bodies[pair.0];
             let b2 = &self.bodies[pair.1];
             let s1 = b1.shape().unwrap();
             let s2 = b2.shape().unwrap();
-            if let Some(contact) = collision_resolver.check_collision(b1.position(), &s1, b2.position(), &s2) {
-                let contact = ContactInfo { first: pair.0, second: pair.1, contact };
-                self.contacts_info.push(contact);
+            self.contacts.clear();
+            collision_resolver.check_collisions(b1.position(), &s1, b2.position(), &s2, &mut self.contacts);
+            for contact in self.contacts.drain(..) {
+                self.contacts_info.push(ContactInfo { first: pair.0, second: pair.1, contact });
             }
         }
+    }
 
+    /// Same contacts and order as the sequential version: the candidate pairs are checked in parallel
+    /// and their contacts collected in the order of the pairs.
+    #[cfg(feature = "parallel")]
+    fn find_contacts(&mut self) {
+        let (bounds, bodies) = (&self.bounds, &self.bodies);
+        let batches: Vec<Vec<ContactInfo<D>>> = self.candidates.par_chunks(CONTACT_BATCH_SIZE)
+            .map_init(|| (CollisionResolver::new(bounds), Vec::new()), |(collision_resolver, contacts), pairs| {
+                let mut contacts_info = Vec::new();
+                for &(first, second) in pairs {
+                    let (b1, b2) = (&bodies[first], &bodies[second]);
+                    collision_resolver.check_collisions(b1.position(), b1.shape().unwrap(), b2.position(), b2.shape().unwrap(), contacts);
+                    contacts_info.extend(contacts.drain(..).map(|contact| ContactInfo { first, second, contact }));
+                }
+                contacts_info
+            })
+            .collect();
+        self.contacts_info.clear();
+        for batch in batches {
+            self.contacts_info.extend(batch);
+        }
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    fn resolve_contacts(&mut self) {
         for ContactInfo { first, second, contact } in &mut self.contacts_info {
             let (b1, b2) = self.bodies.get_two_mut(*first, *second);
             contact.resolve_overlap(b1, b2);
-            contact.resolve_velocity(b1, b2);
+            self.virial += contact.distance() * contact.resolve_velocity(b1, b2);
         }
+    }
 
-        for body in &mut self.bodies {
-            body.set_position(self.bounds.get_toroidal_position(body.position()));
+    /// Contacts that do not share bodies are resolved in parallel, keeping the order of the
+    /// contacts of each body, so the result is the same as the sequential one for any number of threads.
+    #[cfg(feature = "parallel")]
+    fn resolve_contacts(&mut self) {
+        let pairs: Vec<_> = self.contacts_info.iter().map(|info| (info.first, info.second)).collect();
+        let contacts_info = &self.contacts_info;
+        let virials = for_each_pair_mut(&mut self.bodies, &pairs, |index, b1, b2| {
+            let contact = &contacts_info[index].contact;
+            contact.resolve_overlap(b1, b2);
+            contact.distance() * contact.resolve_velocity(b1, b2)
+        });
+        for virial in virials {
+            self.virial += virial;
         }
     }
 }
 
-pub struct ContactInfo {
+impl<const D: usize> Space<Bounds<D>, D> {
+    /// Positions of every periodic image of the body whose AABB intersects the viewport,
+    /// for example to draw the bodies near the edges in several places.
+    /// Bodies without shape are taken as points. See [`Bounds::get_images()`].
+    pub fn body_images(&self, handle: BodyHandle, viewport: &AxisAlignmentBoundingBox<D>) -> Option<Vec<Vector<D>>> {
+        let body = self.body(handle)?;
+        let aabb = body.aabb().unwrap_or_else(|| AxisAlignmentBoundingBox::new(body.position(), Vector::zero()));
+        let images = self.bounds.get_images(&aabb, viewport);
+        Some(images.into_iter().map(|translation| body.position() + translation).collect())
+    }
+}
+
+struct CellSizeTuning {
+    countdown: u32, // updates until the next tuning
+}
+
+impl CellSizeTuning {
+    const INTERVAL: u32 = 100; // updates
+    const DIAMETER_FACTOR: Real = 1.5; // cell size relative to the mean diameter of the shapes
+    const CELLS_PER_BODY: Real = 16.0; // upper bound, to avoid huge grids for tiny shapes
+    const TOLERANCE: Real = 0.2; // relative change of the cell size needed to rebuild the grid
+}
+
+struct Reordering {
+    curve: SpaceFillingCurve,
+    interval: u32, // updates
+    countdown: u32, // updates until the next reordering
+}
+
+struct AdaptiveSubsteps {
+    max_fraction: Real,
+    max_substeps: u32,
+}
+
+impl AdaptiveSubsteps {
+    /// Without cell size, the bodies without shape do not limit the substeps.
+    fn compute<const D: usize>(&self, bodies: &[Body<D>], cell_size: Option<Real>, dt: Real) -> u32 {
+        let cell_size = cell_size.unwrap_or(Real::INFINITY);
+        let required = bodies.iter().fold(1.0 as Real, |required, body| {
+            let size = match body.shape() {
+                Some(shape) => cell_size.min(shape.half_dimension::<D>().min_component() * 2.0),
+                None => cell_size,
+            };
+            required.max(body.velocity().length() * dt / (size * self.max_fraction))
+        });
+        (required.ceil() as u32).clamp(1, self.max_substeps)
+    }
+}
+
+pub struct ContactInfo<const D: usize = 2> {
     first: usize,
     second: usize,
-    contact: Contact,
+    contact: Contact<D>,
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::math::{Vec1, Vec2, Vec3};
+    use super::super::shapes::Shape;
+    use super::super::math::topology::{KleinBottle, TwistedTorus, ProjectivePlane};
+    use super::super::math::toroidal::{Boundary};
+    use super::super::broad_phase::{SweepAndPrune, AabbTree, HierarchicalGrid};
+
+    /// Fills a space of 100x100 with rows of 20 circles of different sizes moving in different directions.
+    fn add_moving_circles(space: &mut Space, rows: usize, speed: Real) {
+        for i in 0..rows * 20 {
+            let mut body = Body::new(Vec2::xy((i % 20) as Real * 5.0, (i / 20) as Real * 100.0 / rows as Real));
+            body.set_shape(Shape::Circle(1.5 + (i % 3) as Real * 0.5));
+            body.set_velocity(Vec2::from_angle(i as Real) * speed);
+            space.add(body);
+        }
+    }
+
+    fn states(space: &Space) -> Vec<(Vec2, Vec2)> {
+        space.bodies().map(|body| (body.position(), body.velocity())).collect()
+    }
+
+    #[test]
+    fn adaptive_substeps_prevent_tunneling() {
+        let build = |space: Space| {
+            let mut space = space.with_optimization_cell_size(10.0);
+            let mut bullet = Body::new(Vec2::xy(10.0, 50.0));
+            bullet.set_shape(Shape::Circle(1.0));
+            bullet.set_velocity(Vec2::x(4000.0));
+            space.add(bullet);
+
+            let mut wall = Body::new(Vec2::xy(40.0, 50.0));
+            wall.set_shape(Shape::Circle(2.0));
+            wall.set_mass(1e6);
+            space.add(wall);
+            space
+        };
+
+        let mut fixed = build(Space::new(100.0, 100.0));
+        fixed.update(Duration::from_millis(10));
+        assert_eq!(fixed.last_substeps(), 1);
+        assert!(fixed.bodies().next().unwrap().velocity().x > 0.0);
+
+        let mut adaptive = build(Space::new(100.0, 100.0).with_adaptive_substeps(0.5, 100));
+        adaptive.update(Duration::from_millis(10));
+        assert_eq!(adaptive.last_substeps(), 40);
+        assert!(adaptive.bodies().next().unwrap().velocity().x < 0.0);
+    }
+
+    #[test]
+    fn forces_apply_along_all_substeps() {
+        let mut space = Space::new(100.0, 100.0).with_adaptive_substeps(0.1, 10);
+        let mut body = Body::new(Vec2::xy(50.0, 50.0));
+        body.set_velocity(Vec2::x(100.0));
+        space.add(body);
+
+        space.bodies_mut().next().unwrap().add_force(Vec2::y(10.0));
+        space.update(Duration::from_millis(100));
+        let body = space.bodies().next().unwrap();
+        assert_eq!(space.last_substeps(), 10);
+        assert!((body.velocity().y - 1.0).abs() < 1e-4);
+        assert_eq!(body.force(), Vec2::zero());
+    }
+
+    #[test]
+    fn static_bodies_do_not_count_in_the_temperature() {
+        let mut space = Space::new(100.0, 100.0);
+        let mut body = Body::new(Vec2::xy(10.0, 10.0));
+        body.set_velocity(Vec2::x(2.0));
+        space.add(body);
+        let mut wall = Body::new(Vec2::xy(50.0, 50.0));
+        wall.set_mass(Real::INFINITY);
+        space.add(wall);
+        assert_eq!(space.kinetic_temperature(), 2.0);
+    }
+
+    #[test]
+    fn resize_rescales_positions() {
+        let mut space = Space::new(100.0, 50.0);
+        space.add(Body::new(Vec2::xy(25.0, 10.0)));
+        space.resize(Vec2::xy(200.0, 25.0));
+        assert_eq!(space.bounds().dimension(), Vec2::xy(200.0, 25.0));
+        assert_eq!(space.bodies().next().unwrap().position(), Vec2::xy(50.0, 5.0));
+    }
+
+    #[test]
+    fn barostat_reaches_ideal_gas_area() {
+        // Ideal gas: P = sum(m * v^2) / (2 * A)
+        let mut space = Space::new(200.0, 200.0).with_barostat(Barostat::new(0.5, 1.0, 0.1));
+        for i in 0..100 {
+            let mut body = Body::new(Vec2::xy(i as Real * 2.0, i as Real));
+            body.set_velocity(Vec2::from_angle(i as Real) * 10.0);
+            space.add(body);
+        }
+        for _ in 0..500 {
+            space.update(Duration::from_millis(10));
+        }
+        let dimension = space.bounds().dimension();
+        assert!((dimension.x - 100.0).abs() <= 2.0, "dimension: {:?}", dimension);
+        assert!((space.pressure() - 0.5).abs() < 0.03, "pressure: {}", space.pressure());
+    }
+
+    #[test]
+    fn static_bodies_keep_the_pressure_finite() {
+        let mut space = Space::new(100.0, 100.0).with_barostat(Barostat::new(0.5, 1.0, 0.1));
+        let mut body = Body::new(Vec2::xy(10.0, 10.0));
+        body.set_velocity(Vec2::x(10.0));
+        space.add(body);
+        let mut wall = Body::new(Vec2::xy(50.0, 50.0));
+        wall.set_mass(Real::INFINITY);
+        space.add(wall);
+
+        space.update(Duration::from_millis(10));
+        assert!((space.pressure() - 0.005).abs() < 1e-6, "pressure: {}", space.pressure());
+        assert!(space.bounds().dimension().x.is_finite());
+    }
+
+    #[test]
+    fn overlapping_static_bodies_are_not_resolved() {
+        let mut space = Space::new(100.0, 100.0);
+        for &x in &[50.0, 51.0] {
+            let mut wall = Body::new(Vec2::xy(x, 50.0));
+            wall.set_shape(Shape::Circle(2.0));
+            wall.set_mass(Real::INFINITY);
+            space.add(wall);
+        }
+        space.update(Duration::from_millis(10));
+        assert_eq!(space.statistics().candidate_pairs, 0);
+        assert_eq!(space.bodies().next().unwrap().position(), Vec2::xy(50.0, 50.0));
+    }
+
+    #[test]
+    fn klein_bottle_mirrors_velocity_across_seam() {
+        let mut space = Space::with_topology(KleinBottle::new(100.0, 50.0));
+        let mut body = Body::new(Vec2::xy(99.0, 10.0));
+        body.set_velocity(Vec2::xy(200.0, 100.0));
+        space.add(body);
+
+        space.update(Duration::from_millis(10));
+        let body = space.bodies().next().unwrap();
+        assert!((body.position().x - 1.0).abs() < 1e-3);
+        assert!((body.position().y - 39.0).abs() < 1e-3);
+        assert_eq!(body.velocity(), Vec2::xy(200.0, -100.0));
+    }
+
+    #[test]
+    fn absorbing_edges_remove_bodies() {
+        let bounds = Bounds::new(100.0, 100.0).with_boundaries(Boundary::Absorbing, Boundary::Periodic);
+        let mut space = Space::with_topology(bounds);
+        let mut leaving = Body::new(Vec2::xy(99.0, 50.0));
+        leaving.set_velocity(Vec2::x(200.0));
+        let leaving = space.add(leaving);
+        let staying = space.add(Body::new(Vec2::xy(50.0, 50.0)));
+
+        space.update(Duration::from_millis(10));
+        assert_eq!(space.bodies().len(), 1);
+        assert!(space.body(leaving).is_none());
+        assert_eq!(space.body(staying).unwrap().position(), Vec2::xy(50.0, 50.0));
+        assert_eq!(space.absorbed().map(|(handle, _)| *handle).collect::<Vec<_>>(), vec![leaving]);
+    }
+
+    #[test]
+    fn reflecting_walls_bounce_with_restitution() {
+        let bounds = Bounds::new(100.0, 100.0).with_boundaries(Boundary::Periodic, Boundary::Reflecting);
+        let mut space = Space::with_topology(bounds);
+        let mut body = Body::new(Vec2::xy(20.0, 1.0));
+        body.set_velocity(Vec2::xy(10.0, -200.0));
+        body.set_restitution(0.5);
+        let handle = space.add(body);
+
+        // The circle bounces when its edge reaches the wall, not its center.
+        let mut circle = Body::new(Vec2::xy(70.0, 6.0));
+        circle.set_shape(Shape::Circle(5.0));
+        circle.set_velocity(Vec2::xy(10.0, -200.0));
+        circle.set_restitution(0.5);
+        let circle_handle = space.add(circle);
+
+        space.update(Duration::from_millis(10));
+        let body = space.body(handle).unwrap();
+        assert!((body.position().y - 1.0).abs() < 1e-4);
+        assert_eq!(body.velocity(), Vec2::xy(10.0, 100.0));
+        let circle = space.body(circle_handle).unwrap();
+        assert!((circle.position().y - 6.0).abs() < 1e-4, "position: {:?}", circle.position());
+        assert_eq!(circle.velocity(), Vec2::xy(10.0, 100.0));
+    }
+
+    #[test]
+    fn walls_do_not_collide_across_edges() {
+        let bounds = Bounds::new(100.0, 100.0).with_boundaries(Boundary::Periodic, Boundary::Reflecting);
+        let mut space = Space::with_topology(bounds).with_optimization_cell_size(10.0);
+        for y in &[2.0, 98.0] {
+            let mut body = Body::new(Vec2::xy(50.0, *y));
+            body.set_shape(Shape::Circle(5.0));
+            space.add(body);
+        }
+        space.update(Duration::from_millis(10));
+        assert!(space.bodies().all(|body| body.velocity() == Vec2::zero()));
+    }
+
+    #[test]
+    fn lees_edwards_shifts_bodies_crossing_the_horizontal_edge() {
+        let mut space = Space::with_topology(Bounds::new(100.0, 100.0).with_lees_edwards(50.0));
+        let mut body = Body::new(Vec2::xy(50.0, 99.0));
+        body.set_velocity(Vec2::y(200.0));
+        let handle = space.add(body);
+
+        space.update(Duration::from_millis(10));
+        let body = space.body(handle).unwrap();
+        assert!((body.position().x - 49.5).abs() < 1e-3, "position: {:?}", body.position());
+        assert!((body.position().y - 1.0).abs() < 1e-3);
+        assert_eq!(body.velocity(), Vec2::xy(-50.0, 200.0));
+    }
+
+    #[test]
+    fn winding_and_unwrapped_position() {
+        let mut space = Space::new(10.0, 10.0);
+        let mut body = Body::new(Vec2::xy(5.0, 5.0));
+        body.set_velocity(Vec2::xy(60.0, -20.0));
+        let handle = space.add(body);
+
+        let mut last = space.unwrapped_position(handle).unwrap();
+        for _ in 0..20 {
+            space.update(Duration::from_millis(25));
+            let unwrapped = space.unwrapped_position(handle).unwrap();
+            assert!((unwrapped - last - Vec2::xy(1.5, -0.5)).length() < 1e-3);
+            last = unwrapped;
+        }
+        assert_eq!(space.body(handle).unwrap().winding(), [3, -1]);
+        assert!((last - Vec2::xy(35.0, -5.0)).length() < 1e-3);
+    }
+
+    #[test]
+    fn seam_crossings_by_integration_and_collision() {
+        let mut space = Space::new(100.0, 100.0);
+        let mut moving = Body::new(Vec2::xy(50.0, 1.0));
+        moving.set_velocity(Vec2::y(-200.0));
+        let moving = space.add(moving);
+
+        // A heavy body pushes a light one across the right edge by the overlap resolution.
+        let mut heavy = Body::new(Vec2::xy(97.0, 50.0));
+        heavy.set_shape(Shape::Circle(2.0));
+        heavy.set_mass(1e6);
+        space.add(heavy);
+        let mut light = Body::new(Vec2::xy(99.5, 50.0));
+        light.set_shape(Shape::Circle(2.0));
+        let light = space.add(light);
+
+        space.update(Duration::from_millis(10));
+        let crossings: Vec<_> = space.seam_crossings().collect();
+        assert_eq!(crossings.len(), 2);
+        assert_eq!((crossings[0].body, crossings[0].axis, crossings[0].direction), (moving, Axis::Y, Direction::Negative));
+        assert!((crossings[0].position_before.y + 1.0).abs() < 1e-3);
+        assert!((crossings[0].position_after.y - 99.0).abs() < 1e-3);
+        assert_eq!((crossings[1].body, crossings[1].axis, crossings[1].direction), (light, Axis::X, Direction::Positive));
+    }
+
+    #[test]
+    fn ring_collisions_across_the_seam() {
+        let mut space = Space::ring(100.0).with_optimization_cell_size(10.0);
+        let mut car = Body::new(Vec1::x(98.5));
+        car.set_shape(Shape::Circle(2.0));
+        car.set_velocity(Vec1::x(100.0));
+        let car = space.add(car);
+        let mut stopped = Body::new(Vec1::x(3.0));
+        stopped.set_shape(Shape::Circle(2.0));
+        let stopped = space.add(stopped);
+
+        space.update(Duration::from_millis(10));
+        assert_eq!(space.body(car).unwrap().velocity(), Vec1::x(0.0));
+        assert_eq!(space.body(stopped).unwrap().velocity(), Vec1::x(100.0));
+    }
+
+    #[test]
+    fn volume_collisions_across_the_seam() {
+        let mut space = Space::volume(100.0, 100.0, 50.0).with_optimization_cell_size(10.0);
+        let mut b1 = Body::new(Vec3::xyz(50.0, 50.0, 0.5));
+        b1.set_shape(Shape::Circle(2.0));
+        b1.set_velocity(Vec3::z(-100.0));
+        let b1 = space.add(b1);
+        let mut b2 = Body::new(Vec3::xyz(50.0, 50.0, 46.0));
+        b2.set_shape(Shape::Circle(2.0));
+        let b2 = space.add(b2);
+
+        space.update(Duration::from_millis(10));
+        assert_eq!(space.body(b1).unwrap().velocity(), Vec3::zero());
+        assert_eq!(space.body(b2).unwrap().velocity(), Vec3::z(-100.0));
+        assert_eq!(space.body(b1).unwrap().winding(), [0, 0, -1]);
+        assert_eq!(space.seam_crossings().map(|crossing| crossing.axis).collect::<Vec<_>>(), vec![Axis::Z]);
+    }
+
+    #[test]
+    fn hexagonal_collisions_across_the_slanted_seam() {
+        let bounds = Bounds::hexagonal(10.0);
+        let height = bounds.lattice().unwrap().vectors()[1].y;
+        let mut space = Space::with_topology(bounds).with_optimization_cell_size(2.0);
+        let mut b1 = Body::new(Vec2::xy(14.5, height - 0.5));
+        b1.set_shape(Shape::Circle(1.0));
+        b1.set_velocity(Vec2::xy(10.0, 10.0));
+        let b1 = space.add(b1);
+        let mut b2 = Body::new(Vec2::xy(0.5, 0.5));
+        b2.set_shape(Shape::Circle(1.0));
+        let b2 = space.add(b2);
+
+        space.update(Duration::from_millis(1));
+        assert!((space.body(b1).unwrap().velocity()).length() < 1e-3);
+        assert!((space.body(b2).unwrap().velocity() - Vec2::xy(10.0, 10.0)).length() < 1e-3);
+    }
+
+    #[test]
+    fn cell_size_follows_the_shapes_and_statistics_count_the_contacts() {
+        let mut space = Space::new(100.0, 100.0);
+        for i in 0..100 {
+            let mut body = Body::new(Vec2::xy((i % 10) as Real * 10.0 + 5.0, (i / 10) as Real * 10.0 + 5.0));
+            body.set_shape(Shape::Circle(1.0));
+            space.add(body);
+        }
+        let mut touching = Body::new(Vec2::xy(6.5, 5.0));
+        touching.set_shape(Shape::Circle(1.0));
+        space.add(touching);
+
+        space.update(Duration::from_millis(1));
+        let statistics = space.statistics();
+        assert_eq!(statistics.cell_size, Some(3.0)); // 1.5 times the mean diameter
+        assert_eq!(statistics.occupancy.unwrap().max_bodies_per_cell, 2);
+        assert_eq!(statistics.candidate_pairs, 1);
+        assert_eq!(statistics.contacts, 1);
+        assert!(statistics.broad_phase_pairs >= 1);
+
+        let space = Space::new(100.0, 100.0).with_optimization_cell_size(20.0);
+        assert_eq!(space.statistics().cell_size, Some(20.0));
+    }
+
+    #[test]
+    fn every_broad_phase_finds_collisions_across_the_corner() {
+        fn collide<B: BroadPhase + 'static>(broad_phase: B) {
+            let mut space = Space::new(100.0, 50.0).with_broad_phase(broad_phase);
+            let mut planet = Body::new(Vec2::xy(50.0, 25.0));
+            planet.set_shape(Shape::Circle(10.0));
+            space.add(planet);
+            let mut b1 = Body::new(Vec2::xy(99.5, 49.5));
+            b1.set_shape(Shape::Circle(1.0));
+            b1.set_velocity(Vec2::xy(10.0, 10.0));
+            let b1 = space.add(b1);
+            let mut b2 = Body::new(Vec2::xy(0.5, 0.5));
+            b2.set_shape(Shape::Circle(1.0));
+            let b2 = space.add(b2);
+
+            for _ in 0..3 {
+                space.update(Duration::from_millis(1));
+            }
+            assert!((space.body(b1).unwrap().velocity()).length() < 1e-3);
+            assert!((space.body(b2).unwrap().velocity() - Vec2::xy(10.0, 10.0)).length() < 1e-3);
+        }
+        collide(SpatialTable::new(Vec2::zero(), 5.0));
+        collide(SweepAndPrune::new());
+        collide(AabbTree::new());
+        collide(HierarchicalGrid::new());
+    }
+
+    #[test]
+    fn reordering_keeps_the_handles() {
+        let mut space = Space::new(128.0, 128.0).with_body_reordering(SpaceFillingCurve::Hilbert, 10);
+        let handles: Vec<_> = (0..64).map(|i| {
+            let cell = i * 37 % 64;
+            space.add(Body::new(Vec2::xy((cell % 8) as Real * 16.0 + 8.0, (cell / 8) as Real * 16.0 + 8.0)))
+        }).collect();
+        let positions: Vec<_> = space.bodies().map(|body| body.position()).collect();
+
+        space.update(Duration::from_millis(10));
+        for (handle, position) in handles.iter().zip(positions) {
+            assert_eq!(space.body(*handle).unwrap().position(), position);
+        }
+        let bodies: Vec<_> = space.bodies().collect();
+        assert!(bodies.windows(2).all(|pair| (pair[0].position() - pair[1].position()).length() == 16.0));
+        assert!(space.handles().zip(space.bodies()).all(|(handle, body)| std::ptr::eq(space.body(*handle).unwrap(), body)));
+    }
+
+    #[test]
+    fn neighbor_lists_find_the_same_contacts() {
+        let simulate = |skin: Option<Real>| {
+            let mut space = Space::new(100.0, 100.0).with_broad_phase(SweepAndPrune::new());
+            if let Some(skin) = skin {
+                space = space.with_neighbor_lists(skin);
+            }
+            add_moving_circles(&mut space, 10, 20.0);
+            let mut builds = 0;
+            for _ in 0..100 {
+                space.update(Duration::from_millis(10));
+                builds += space.statistics().neighbor_list_builds;
+            }
+            (states(&space), builds, space.neighbor_pairs().count())
+        };
+        let (reference, _, _) = simulate(None);
+        let (states, builds, neighbors) = simulate(Some(2.0));
+        assert_eq!(states, reference);
+        assert!(builds > 1 && builds < 50, "builds: {}", builds);
+        assert!(neighbors > 0);
+    }
+
+    #[cfg(feature = "deterministic")]
+    #[test]
+    fn deterministic_step_does_not_depend_on_the_broad_phase() {
+        fn simulate<B: BroadPhase + 'static>(broad_phase: Option<B>) -> Vec<(Vec2, Vec2)> {
+            let thermostat = Thermostat::Langevin { temperature: 10.0, friction: 1.0 };
+            let mut space = Space::new(100.0, 100.0).with_thermostat(thermostat, 3);
+            if let Some(broad_phase) = broad_phase {
+                space = space.with_broad_phase(broad_phase);
+            }
+            add_moving_circles(&mut space, 10, 30.0);
+            for _ in 0..50 {
+                space.update(Duration::from_millis(10));
+            }
+            states(&space)
+        }
+        let reference = simulate::<SpatialTable>(None);
+        assert_eq!(simulate(Some(SweepAndPrune::new())), reference);
+        assert_eq!(simulate(Some(AabbTree::new())), reference);
+        assert_eq!(simulate(Some(HierarchicalGrid::new())), reference);
+    }
+
+    #[test]
+    fn body_images_in_a_larger_viewport() {
+        let mut space = Space::new(100.0, 100.0);
+        let handle = space.add(Body::new(Vec2::xy(10.0, 50.0)));
+        let viewport = AxisAlignmentBoundingBox::from_bounds(0.0, 250.0, 0.0, 100.0);
+        let images = space.body_images(handle, &viewport).unwrap();
+        assert_eq!(images, vec![Vec2::xy(10.0, 50.0), Vec2::xy(110.0, 50.0), Vec2::xy(210.0, 50.0)]);
+    }
+
+    #[test]
+    fn contacts_through_two_images_in_a_small_world() {
+        let mut space = Space::new(10.0, 10.0).with_optimization_cell_size(5.0);
+        for x in &[2.0, 8.0] {
+            let mut body = Body::new(Vec2::xy(*x, 5.0));
+            body.set_shape(Shape::Circle(3.5));
+            space.add(body);
+        }
+        space.update(Duration::from_millis(10));
+
+        // Touching directly (overlap 1) and through the vertical edges (overlap 3).
+        let mut overlaps: Vec<_> = space.contacts_info.iter().map(|info| info.contact.overlap()).collect();
+        overlaps.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(overlaps.len(), 2);
+        assert!((overlaps[0] - 1.0).abs() < 1e-4 && (overlaps[1] - 3.0).abs() < 1e-4, "overlaps: {:?}", overlaps);
+    }
+
+    #[test]
+    fn contacts_through_two_images_in_small_non_orientable_worlds() {
+        fn overlaps<T: Topology>(topology: T) -> Vec<Real> {
+            let mut space = Space::with_topology(topology).with_optimization_cell_size(5.0);
+            for x in &[2.0, 8.0] {
+                let mut body = Body::new(Vec2::xy(*x, 5.0));
+                body.set_shape(Shape::Circle(3.5));
+                space.add(body);
+            }
+            space.update(Duration::from_millis(10));
+            let mut overlaps: Vec<_> = space.contacts_info.iter().map(|info| info.contact.overlap()).collect();
+            overlaps.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            overlaps
+        }
+
+        // The flipped image across the vertical edges is at the same height.
+        for overlaps in [overlaps(KleinBottle::new(10.0, 10.0)), overlaps(ProjectivePlane::new(10.0, 10.0))] {
+            assert_eq!(overlaps.len(), 2);
+            assert!((overlaps[0] - 1.0).abs() < 1e-4 && (overlaps[1] - 3.0).abs() < 1e-4, "overlaps: {:?}", overlaps);
+        }
+
+        // The image across the vertical edges is 2 units lower.
+        let overlaps = overlaps(TwistedTorus::new(10.0, 10.0, 2.0));
+        assert_eq!(overlaps.len(), 2);
+        assert!((overlaps[0] - 1.0).abs() < 1e-4 && (overlaps[1] - (7.0 - (20.0 as Real).sqrt())).abs() < 1e-4, "overlaps: {:?}", overlaps);
+    }
+
+    #[test]
+    fn bodies_larger_than_half_the_world_collide() {
+        let mut space = Space::new(100.0, 100.0).with_optimization_cell_size(10.0);
+        let mut big = Body::new(Vec2::xy(50.0, 50.0));
+        big.set_shape(Shape::Circle(60.0));
+        big.set_mass(1e6);
+        space.add(big);
+        let mut small = Body::new(Vec2::xy(5.0, 50.0));
+        small.set_shape(Shape::Circle(1.0));
+        small.set_velocity(Vec2::x(10.0));
+        let small = space.add(small);
+
+        // The AABB of the big body covers the whole world, so the small body is found by the broad phase.
+        space.update(Duration::from_millis(10));
+        assert!(!space.contacts_info.is_empty());
+        assert!(space.body(small).unwrap().velocity().x < 0.0);
+    }
+
+    #[test]
+    fn bodies_taller_than_the_space_collide_in_every_topology() {
+        fn collide<T: Topology + 'static>(topology: T) {
+            let mut space = Space::with_topology(topology).with_broad_phase(SweepAndPrune::new());
+            let mut big = Body::new(Vec2::xy(50.0, 10.0));
+            big.set_shape(Shape::Circle(15.0));
+            space.add(big);
+            let mut small = Body::new(Vec2::xy(60.0, 10.0));
+            small.set_shape(Shape::Circle(1.0));
+            space.add(small);
+
+            space.update(Duration::from_millis(1));
+            assert!(!space.contacts_info.is_empty());
+        }
+        collide(Bounds::new(100.0, 20.0));
+        collide(KleinBottle::new(100.0, 20.0));
+        collide(TwistedTorus::new(100.0, 20.0, 7.0));
+        collide(ProjectivePlane::new(100.0, 20.0));
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_step_does_not_depend_on_the_thread_count() {
+        let simulate = |threads: usize| {
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+            pool.install(|| {
+                let mut space = Space::new(100.0, 100.0);
+                space.add_force_field(|_: &Body, position: Vec2, _: Vec2| (Vec2::xy(50.0, 50.0) - position) * 0.1);
+                add_moving_circles(&mut space, 20, 20.0);
+                for _ in 0..100 {
+                    space.update(Duration::from_millis(10));
+                }
+                states(&space)
+            })
+        };
+        let reference = simulate(1);
+        assert_eq!(simulate(2), reference);
+        assert_eq!(simulate(8), reference);
+    }
+}
@@ -1,73 +1,381 @@
-use super::math::bounding::{AxisAlignmentBoundingBox};
+use super::math::{Vector, Real, bounding::{AxisAlignmentBoundingBox}};
+use super::broad_phase::{BroadPhase, GridOccupancy};
 
-use std::slice::{Iter};
-use std::collections::HashSet;
+/// The table is rebuilt from scratch when more than one of this many bodies cover other cells:
+/// finding the pairs of every body is then faster than replacing the pairs of the moved ones.
+const MAX_MOVED_FRACTION: usize = 8;
 
-pub struct SpatialTable {
-    width: u32,
-    height: u32,
-    inverse_cell_size: f32,
-    cells: Vec<Vec<usize>>,
+/// Uniform grid that finds the pairs of bodies sharing some cell.
+/// The cell size is a heuristic for performance: a value between 1 or 2 times the average size of the shapes works fine.
+/// The cells are flat arrays built with a counting sort: the bodies of the cell `c`
+/// are `cell_bodies[cell_start[c]..cell_start[c] + cell_lengths[c]]`.
+/// Each cell is built with some free room, so a body that covers other cells than before
+/// is moved alone between the cells, and only its pairs are found again.
+/// The arrays are rebuilt from scratch when some cell runs out of room or many bodies move.
+pub struct SpatialTable<const D: usize = 2> {
+    cell_counts: [u32; D],
+    inverse_cell_size: Real,
+    cell_start: Vec<usize>, // first slot of each cell in cell_bodies
+    cell_lengths: Vec<usize>, // number of bodies of each cell, up to the start of the next cell
+    cell_bodies: Vec<usize>,
+    ranges: Vec<Vec<CellRange<D>>>, // cell ranges of the AABBs of each body
+    covered: Vec<Vec<usize>>, // sorted cells covered by each body
+    boxes: Vec<Option<CellRange<D>>>, // range of the bodies covering a box of cells that does not wrap, stored for performance
     pairs: Vec<(usize, usize)>,
-    checked: HashSet<usize> // Stored for performance
+    moved: Vec<usize>, // bodies covering other cells since the last pairs
+    is_moved: Vec<bool>,
+    rebuild: bool,
+    new_ranges: Vec<CellRange<D>>, // Stored for performance
+    new_covered: Vec<usize>, // Stored for performance
 }
 
-impl SpatialTable {
-    pub fn new(space_width: u32, space_height: u32, cell_size: f32) -> SpatialTable {
+/// First cell and number of cells covered in each axis by an AABB.
+#[derive(Clone, Copy, PartialEq)]
+struct CellRange<const D: usize> {
+    first: [u32; D],
+    lengths: [u32; D],
+}
+
+impl<const D: usize> SpatialTable<D> {
+    pub fn new(space_dimension: Vector<D>, cell_size: Real) -> SpatialTable<D> {
         let inverse_cell_size = 1.0 / cell_size;
-        let width = (space_width as f32 * inverse_cell_size) as u32 + if space_width % cell_size as u32 > 0 { 1 } else { 0 };
-        let height = (space_height as f32 * inverse_cell_size) as u32 + if space_height % cell_size as u32 > 0 { 1 } else { 0 };
+        let mut cell_counts = [1; D];
+        for (axis, count) in cell_counts.iter_mut().enumerate() {
+            *count = ((space_dimension[axis] * inverse_cell_size).ceil() as u32).max(1);
+        }
+        let cell_count = cell_counts.iter().product::<u32>() as usize;
         SpatialTable {
-            width,
-            height,
+            cell_counts,
             inverse_cell_size,
-            cells: (0..width * height).map(|_| Vec::new()).collect(),
+            cell_start: vec![0; cell_count + 1],
+            cell_lengths: vec![0; cell_count],
+            cell_bodies: Vec::new(),
+            ranges: Vec::new(),
+            covered: Vec::new(),
+            boxes: Vec::new(),
             pairs: Vec::new(),
-            checked: HashSet::new(),
+            moved: Vec::new(),
+            is_moved: Vec::new(),
+            rebuild: true,
+            new_ranges: Vec::new(),
+            new_covered: Vec::new(),
         }
     }
 
-    pub fn pairs(&self) -> Iter<(usize, usize)> {
-        self.pairs.iter()
+    pub fn cell_size(&self) -> Real {
+        1.0 / self.inverse_cell_size
     }
 
-    pub fn transform_coordinate(&self, coordinate: f32) -> u32 {
+    pub fn transform_coordinate(&self, coordinate: Real) -> u32 {
         (coordinate * self.inverse_cell_size) as u32
     }
 
-    pub fn clear(&mut self) {
-        for cell in &mut self.cells {
-            cell.clear();
+    /// Counting sort of the covered cells of every body into the flat cell arrays,
+    /// leaving room in each cell for the bodies that will enter it.
+    fn build_cells(&mut self) {
+        self.cell_lengths.iter_mut().for_each(|length| *length = 0);
+        for &index in self.covered.iter().flatten() {
+            self.cell_lengths[index] += 1;
+        }
+        self.cell_start[0] = 0;
+        for index in 0..self.cell_lengths.len() {
+            let length = self.cell_lengths[index];
+            self.cell_start[index + 1] = self.cell_start[index] + length + length / 2 + 2;
         }
 
+        let (cell_start, cell_lengths, cell_bodies) = (&self.cell_start, &mut self.cell_lengths, &mut self.cell_bodies);
+        cell_lengths.iter_mut().for_each(|length| *length = 0);
+        cell_bodies.resize(*cell_start.last().unwrap(), 0);
+        for (id, covered) in self.covered.iter().enumerate() {
+            for &index in covered {
+                cell_bodies[cell_start[index] + cell_lengths[index]] = id;
+                cell_lengths[index] += 1;
+            }
+        }
+    }
+
+    /// Each pair is reported in the first cell shared by both bodies,
+    /// so it is found only once without remembering the reported pairs.
+    fn build_pairs(&mut self) {
         self.pairs.clear();
+        for index in 0..self.cell_lengths.len() {
+            let cell = cell(&self.cell_bodies, self.cell_start[index], self.cell_lengths[index]);
+            for (position, &first) in cell.iter().enumerate() {
+                for &second in &cell[position + 1..] {
+                    if self.first_shared_cell(first, second) == index {
+                        self.pairs.push((first.max(second), first.min(second)));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Replaces the pairs of the moved bodies, keeping the pairs of the other ones in the same order.
+    /// A pair of two moved bodies is found from the higher id.
+    fn update_pairs(&mut self) {
+        let is_moved = &self.is_moved;
+        self.pairs.retain(|&(first, second)| !is_moved[first] && !is_moved[second]);
+
+        for &id in &self.moved {
+            for &index in &self.covered[id] {
+                for &other in cell(&self.cell_bodies, self.cell_start[index], self.cell_lengths[index]) {
+                    let lower_or_still = other < id || (other > id && !is_moved[other]);
+                    if lower_or_still && self.first_shared_cell(id, other) == index {
+                        self.pairs.push((id.max(other), id.min(other)));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Moves a body between the cells, or marks the table for a rebuild if a cell is full.
+    fn move_body(&mut self, id: usize, old_covered: &[usize], new_covered: &[usize]) {
+        for &index in old_covered {
+            if new_covered.binary_search(&index).is_err() {
+                let start = self.cell_start[index];
+                let cell = &mut self.cell_bodies[start..start + self.cell_lengths[index]];
+                let position = cell.iter().position(|stored_id| *stored_id == id).unwrap();
+                cell.swap(position, cell.len() - 1);
+                self.cell_lengths[index] -= 1;
+            }
+        }
+        for &index in new_covered {
+            if old_covered.binary_search(&index).is_err() {
+                let slot = self.cell_start[index] + self.cell_lengths[index];
+                if slot == self.cell_start[index + 1] {
+                    self.rebuild = true;
+                    return
+                }
+                self.cell_bodies[slot] = id;
+                self.cell_lengths[index] += 1;
+            }
+        }
     }
 
-    pub fn insert(&mut self, id: usize, aabb: &AxisAlignmentBoundingBox) {
-        let left = self.transform_coordinate(aabb.left());
-        let right = self.transform_coordinate(aabb.right());
-        let top = self.transform_coordinate(aabb.top());
-        let bottom = self.transform_coordinate(aabb.bottom());
+    /// Lowest cell shared by two bodies that share some cell.
+    fn first_shared_cell(&self, first: usize, second: usize) -> usize {
+        match (&self.boxes[first], &self.boxes[second]) {
+            (Some(first), Some(second)) => {
+                // The boxes overlap from their highest first cell in each axis.
+                let mut index = 0;
+                for axis in (0..D).rev() {
+                    let coordinate = first.first[axis].max(second.first[axis]) + 1;
+                    index = index * self.cell_counts[axis] as usize + coordinate as usize;
+                }
+                index
+            },
+            _ => first_shared_cell(&self.covered[first], &self.covered[second]).unwrap(),
+        }
+    }
 
-        let x_length = 1 + if left <= right {right - left} else {right + self.width - left };
-        let y_length = 1 + if top <= bottom {bottom - top} else {bottom + self.height - top };
+    /// Cells covered by the AABB, wrapping around the last cell.
+    /// The AABBs as wide as the space cover the whole row (or column) once.
+    fn cell_range(&self, aabb: &AxisAlignmentBoundingBox<D>) -> CellRange<D> {
+        let (min, max) = (aabb.min(), aabb.max());
+        let mut range = CellRange { first: [0; D], lengths: [0; D] };
+        for axis in 0..D {
+            let start = self.transform_coordinate(min[axis]);
+            let end = self.transform_coordinate(max[axis]);
+            let wraps = min[axis] > max[axis];
+            let length = 1 + if wraps { end + self.cell_counts[axis] - start } else { end - start };
+            range.first[axis] = start;
+            range.lengths[axis] = length.min(self.cell_counts[axis]);
+        }
+        range
+    }
 
-        self.checked.clear();
+    /// Pushes the indices of the cells of the range.
+    fn covered_cells(&self, range: &CellRange<D>, output: &mut Vec<usize>) {
+        let mut offsets = [0; D];
+        'cells: loop {
+            let mut index = 0;
+            for axis in (0..D).rev() {
+                let coordinate = (range.first[axis] + offsets[axis] + 1) % self.cell_counts[axis];
+                index = index * self.cell_counts[axis] as usize + coordinate as usize;
+            }
+            output.push(index);
 
-        let mut x = left;
-        for _ in 0..x_length {
-            x = (x + 1) % self.width;
-            let mut y = top;
-            for _ in 0..y_length {
-                y = (y + 1) % self.height;
-                let cell = &mut self.cells[(y * self.width + x) as usize];
-                for stored_id in cell.iter() {
-                    if self.checked.insert(*stored_id) {
-                        self.pairs.push((id, *stored_id));
-                    }
+            for axis in (0..D).rev() {
+                offsets[axis] += 1;
+                if offsets[axis] < range.lengths[axis] {
+                    continue 'cells
                 }
-                cell.push(id);
+                offsets[axis] = 0;
+            }
+            break
+        }
+    }
+}
+
+/// Lowest cell of two sorted lists of cells.
+fn first_shared_cell(first: &[usize], second: &[usize]) -> Option<usize> {
+    let (mut i, mut j) = (0, 0);
+    while i < first.len() && j < second.len() {
+        match first[i].cmp(&second[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => return Some(first[i]),
+        }
+    }
+    None
+}
+
+fn cell(cell_bodies: &[usize], start: usize, length: usize) -> &[usize] {
+    &cell_bodies[start..start + length]
+}
+
+impl<const D: usize> BroadPhase<D> for SpatialTable<D> {
+    fn resize(&mut self, dimension: Vector<D>) {
+        *self = SpatialTable::new(dimension, self.cell_size());
+    }
+
+    fn clear(&mut self) {
+        self.ranges.clear();
+        self.covered.clear();
+        self.boxes.clear();
+        self.rebuild = true;
+    }
+
+    /// Only moves the body if the ranges of cells of its AABBs change.
+    fn update(&mut self, id: usize, aabbs: &[AxisAlignmentBoundingBox<D>]) {
+        if id >= self.covered.len() {
+            self.ranges.resize_with(id + 1, Vec::new);
+            self.covered.resize_with(id + 1, Vec::new);
+            self.boxes.resize(id + 1, None);
+            self.is_moved.resize(id + 1, false);
+        }
+        // Most bodies stay in the same cells: comparing the ranges avoids listing the cells.
+        let mut new_ranges = std::mem::take(&mut self.new_ranges);
+        new_ranges.clear();
+        new_ranges.extend(aabbs.iter().map(|aabb| self.cell_range(aabb)));
+        if self.ranges[id] == new_ranges {
+            self.new_ranges = new_ranges;
+            return
+        }
+        self.boxes[id] = match new_ranges[..] {
+            [range] if (0..D).all(|axis| range.first[axis] + range.lengths[axis] < self.cell_counts[axis]) => Some(range),
+            _ => None,
+        };
+        self.new_ranges = std::mem::replace(&mut self.ranges[id], new_ranges);
+
+        let mut new_covered = std::mem::take(&mut self.new_covered);
+        new_covered.clear();
+        for range in &self.ranges[id] {
+            self.covered_cells(range, &mut new_covered);
+        }
+        new_covered.sort_unstable();
+        new_covered.dedup();
+
+        if self.covered[id] != new_covered {
+            let old_covered = std::mem::take(&mut self.covered[id]);
+            if self.moved.len() > self.covered.len() / MAX_MOVED_FRACTION {
+                self.rebuild = true;
+            }
+            if !self.rebuild {
+                self.move_body(id, &old_covered, &new_covered);
+            }
+            self.covered[id] = new_covered;
+            if !self.is_moved[id] {
+                self.is_moved[id] = true;
+                self.moved.push(id);
+            }
+            self.new_covered = old_covered;
+        }
+        else {
+            self.new_covered = new_covered;
+        }
+    }
+
+    fn pairs(&mut self) -> &[(usize, usize)] {
+        if self.rebuild {
+            self.build_cells();
+            self.build_pairs();
+            self.rebuild = false;
+        }
+        else if !self.moved.is_empty() {
+            self.update_pairs();
+        }
+        for &id in &self.moved {
+            self.is_moved[id] = false;
+        }
+        self.moved.clear();
+        &self.pairs
+    }
+
+    fn cell_size(&self) -> Option<Real> {
+        Some(SpatialTable::cell_size(self))
+    }
+
+    fn occupancy(&self) -> Option<GridOccupancy> {
+        Some(GridOccupancy::from_cell_sizes(self.cell_lengths.iter().copied()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::math::Vec2;
+
+    fn aabb(x: Real, y: Real) -> AxisAlignmentBoundingBox {
+        AxisAlignmentBoundingBox::new(Vec2::xy(x, y), Vec2::xy(1.0, 1.0))
+    }
+
+    /// AABB in a space of 100x100 that wraps around the edges.
+    fn wrapped_aabb(x: Real, y: Real) -> AxisAlignmentBoundingBox {
+        let wrap = |coordinate: Real| coordinate.rem_euclid(100.0);
+        AxisAlignmentBoundingBox::from_corners(Vec2::xy(wrap(x - 1.0), wrap(y - 1.0)), Vec2::xy(wrap(x + 1.0), wrap(y + 1.0)))
+    }
+
+    #[test]
+    fn pairs_follow_the_moving_bodies() {
+        let mut table = SpatialTable::new(Vec2::xy(100.0, 100.0), 10.0);
+        table.update(0, &[aabb(15.0, 15.0)]);
+        table.update(1, &[aabb(35.0, 15.0)]);
+        table.update(2, &[aabb(15.0, 15.5)]);
+        assert_eq!(table.pairs(), &[(2, 0)]);
+
+        // Moving inside the same cells keeps the pairs.
+        table.update(2, &[aabb(15.5, 15.0)]);
+        assert_eq!(table.pairs(), &[(2, 0)]);
+
+        table.update(0, &[aabb(35.0, 15.5)]);
+        assert_eq!(table.pairs(), &[(1, 0)]);
+
+        table.update(1, &[]);
+        assert_eq!(table.pairs().len(), 0);
+    }
+
+    #[test]
+    fn pairs_are_counted_once_across_shared_cells() {
+        let mut table = SpatialTable::new(Vec2::xy(100.0, 100.0), 10.0);
+        table.update(0, &[aabb(20.0, 20.0)]); // Covers four cells
+        table.update(1, &[aabb(20.5, 20.5)]);
+        assert_eq!(table.pairs(), &[(1, 0)]);
+
+        // It leaves some of the shared cells, but not all of them.
+        table.update(1, &[aabb(21.5, 21.5)]);
+        assert_eq!(table.pairs(), &[(1, 0)]);
+    }
+
+    #[test]
+    fn moving_bodies_give_the_pairs_of_a_new_table() {
+        let mut table = SpatialTable::new(Vec2::xy(100.0, 100.0), 10.0);
+        for step in 0..50 {
+            // Bodies crowding into a few cells run them out of room, some of them across the edges.
+            let position = |id: usize| {
+                let angle = (id * 7 + step * (id % 5)) as Real;
+                Vec2::xy(50.0 + angle.cos() * 50.0 / (1 + step % 3) as Real, 50.0 + angle.sin() * 50.0)
+            };
+            let mut new_table = SpatialTable::new(Vec2::xy(100.0, 100.0), 10.0);
+            for id in 0..60 {
+                table.update(id, &[wrapped_aabb(position(id).x, position(id).y)]);
+                new_table.update(id, &[wrapped_aabb(position(id).x, position(id).y)]);
             }
+            let mut pairs = table.pairs().to_vec();
+            let mut new_pairs = new_table.pairs().to_vec();
+            pairs.sort_unstable();
+            new_pairs.sort_unstable();
+            assert_eq!(pairs, new_pairs);
         }
     }
 }
@@ -0,0 +1,43 @@
+use super::math::{Real};
+use super::broad_phase::{GridOccupancy};
+
+use std::time::Duration;
+
+/// Measures of the collision detection and of the time spent in each phase
+/// during the last [`crate::Space::update()`], summed over its substeps.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Statistics {
+    /// Cell size of the broad phase, if it is a grid.
+    pub cell_size: Option<Real>,
+
+    /// Occupation of the cells of the broad phase after the last substep, if it is a grid.
+    pub occupancy: Option<GridOccupancy>,
+
+    /// Pairs of bodies reported by the broad phase, or by the neighbor list if it is enabled.
+    pub broad_phase_pairs: usize,
+
+    /// Times the neighbor list was rebuilt from the broad phase, if it is enabled.
+    pub neighbor_list_builds: usize,
+
+    /// Pairs checked by the narrow phase: the pairs of the broad phase with some body of finite mass.
+    pub candidate_pairs: usize,
+
+    /// Contacts found by the narrow phase. A pair can touch through several images.
+    pub contacts: usize,
+
+    pub integration_time: Duration,
+    pub broad_phase_time: Duration,
+    pub narrow_phase_time: Duration,
+    pub contact_resolution_time: Duration,
+}
+
+impl Statistics {
+    /// Fraction of the pairs reported by the broad phase that end in contact.
+    /// Low values mean that the broad phase is too coarse for the scene.
+    pub fn contact_ratio(&self) -> Real {
+        if self.broad_phase_pairs == 0 {
+            return 0.0
+        }
+        self.contacts as Real / self.broad_phase_pairs as Real
+    }
+}
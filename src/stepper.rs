@@ -0,0 +1,151 @@
+use super::math::{Vector, Real, topology::Topology};
+use super::space::{Space, BodyHandle};
+
+use std::time::Duration;
+
+/// Drives [`Space::update()`] from wall-clock time with a fixed physics step.
+/// The elapsed time is accumulated and consumed in fixed steps.
+/// The remainder is exposed as an interpolation factor to render the bodies
+/// between the previous and the current physics state.
+pub struct FixedStepper<const D: usize = 2> {
+    step: Duration,
+    max_steps: u32,
+    accumulator: Duration,
+    previous_positions: Vec<Option<Vector<D>>>, // indexed by handle id
+}
+
+impl<const D: usize> FixedStepper<D> {
+    /// Creates a stepper that updates the space with the given step duration.
+    /// By default, at most 5 steps are run per [`FixedStepper::advance()`] call.
+    pub fn new(step: Duration) -> FixedStepper<D> {
+        assert!(step > Duration::from_secs(0), "The step duration must be greater than zero");
+        FixedStepper {
+            step,
+            max_steps: 5,
+            accumulator: Duration::from_secs(0),
+            previous_positions: Vec::new(),
+        }
+    }
+
+    /// A convenient method to modify the maximum number of steps run per advance call.
+    /// When the simulation can not keep up with the wall-clock (spiral of death),
+    /// the time that exceeds this limit is discarded and the simulation runs slower than real time.
+    pub fn with_max_steps(mut self, max_steps: u32) -> FixedStepper<D> {
+        self.max_steps = max_steps.max(1);
+        self
+    }
+
+    pub fn step(&self) -> Duration {
+        self.step
+    }
+
+    pub fn max_steps(&self) -> u32 {
+        self.max_steps
+    }
+
+    /// Accumulates the elapsed wall-clock time and runs as many fixed steps as fit in it.
+    /// Returns the number of steps run.
+    pub fn advance<T: Topology<D>>(&mut self, space: &mut Space<T, D>, elapsed: Duration) -> u32 {
+        self.accumulator += elapsed;
+
+        let mut steps = 0;
+        while self.accumulator >= self.step && steps < self.max_steps {
+            let capacity = space.handles().map(|handle| handle.id() + 1).max().unwrap_or(0);
+            self.previous_positions.clear();
+            self.previous_positions.resize(capacity, None);
+            for (handle, body) in space.handles().zip(space.bodies()) {
+                self.previous_positions[handle.id()] = Some(body.position());
+            }
+            space.update(self.step);
+            self.accumulator -= self.step;
+            steps += 1;
+        }
+
+        if self.accumulator >= self.step {
+            // Catch-up capped: the remaining time is dropped, keeping only the fraction of a step.
+            self.accumulator = Duration::from_nanos((self.accumulator.as_nanos() % self.step.as_nanos()) as u64);
+        }
+
+        steps
+    }
+
+    /// Fraction of a step accumulated but not simulated yet, in the range [0, 1).
+    /// Use it to interpolate between the previous and the current positions.
+    pub fn alpha(&self) -> Real {
+        self.accumulator.as_secs_f64() as Real / self.step.as_secs_f64() as Real
+    }
+
+    /// Position of the body before the last step run.
+    /// Bodies added after the last step have no previous position.
+    pub fn previous_position(&self, handle: BodyHandle) -> Option<Vector<D>> {
+        *self.previous_positions.get(handle.id())?
+    }
+
+    /// Position of the body interpolated with [`FixedStepper::alpha()`]
+    /// between its previous and its current position.
+    /// The interpolation follows the shortest toroidal path,
+    /// so bodies crossing the bounds are not drawn across the whole space.
+    pub fn interpolated_position<T: Topology<D>>(&self, space: &Space<T, D>, handle: BodyHandle) -> Option<Vector<D>> {
+        let current = space.body(handle)?.position();
+        Some(self.interpolate(space.bounds(), handle, current))
+    }
+
+    /// Interpolated positions of all the bodies of the space, in the same order as [`Space::bodies()`].
+    pub fn interpolated_positions<'a, T: Topology<D>>(&'a self, space: &'a Space<T, D>) -> impl Iterator<Item = Vector<D>> + 'a {
+        space.handles().zip(space.bodies())
+            .map(move |(handle, body)| self.interpolate(space.bounds(), *handle, body.position()))
+    }
+
+    fn interpolate<T: Topology<D>>(&self, topology: &T, handle: BodyHandle, current: Vector<D>) -> Vector<D> {
+        match self.previous_position(handle) {
+            Some(previous) => {
+                let path = topology.displacement(current, previous).0;
+                topology.normalize(current - path * (1.0 - self.alpha())).0
+            },
+            None => current,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::body::Body;
+    use super::super::math::Vec2;
+
+    #[test]
+    fn fixed_steps_and_alpha() {
+        let mut space = Space::new(100.0, 100.0);
+        space.add(Body::new(Vec2::zero()));
+
+        let mut stepper = FixedStepper::new(Duration::from_millis(10));
+        assert_eq!(stepper.advance(&mut space, Duration::from_millis(35)), 3);
+        assert!((stepper.alpha() - 0.5).abs() < 1e-4);
+        assert_eq!(stepper.advance(&mut space, Duration::from_millis(5)), 1);
+        assert!(stepper.alpha() < 1e-4);
+    }
+
+    #[test]
+    fn catch_up_is_capped() {
+        let mut space = Space::new(100.0, 100.0);
+        let mut stepper = FixedStepper::new(Duration::from_millis(10)).with_max_steps(4);
+        assert_eq!(stepper.advance(&mut space, Duration::from_millis(1005)), 4);
+        assert!((stepper.alpha() - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn interpolation_takes_shortest_toroidal_path() {
+        let mut space = Space::new(100.0, 100.0);
+        let mut body = Body::new(Vec2::xy(98.0, 50.0));
+        body.set_velocity(Vec2::x(400.0));
+        let handle = space.add(body);
+
+        let mut stepper = FixedStepper::new(Duration::from_millis(10));
+        stepper.advance(&mut space, Duration::from_millis(15));
+        assert_eq!(space.body(handle).unwrap().position(), Vec2::xy(2.0, 50.0));
+
+        let position = stepper.interpolated_position(&space, handle).unwrap();
+        assert!((position.x - 0.0).abs() < 1e-3 || (position.x - 100.0).abs() < 1e-3, "x: {}", position.x);
+        assert_eq!(stepper.interpolated_positions(&space).next(), Some(position));
+    }
+}
@@ -0,0 +1,99 @@
+use super::math::{Vector, Real, functions::exp};
+use super::body::{Body, Particle};
+use super::integrator::{Integrator, ForceField};
+use super::random::{Random};
+
+/// Stochastic integration modes that couple the bodies to a heat bath.
+/// Temperatures are expressed in energy units (the Boltzmann constant is taken as 1).
+/// The friction is a rate (1 / seconds): the inverse of the velocity relaxation time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Thermostat {
+    /// Underdamped Langevin dynamics.
+    /// The bodies are integrated as usual and afterwards their velocities receive
+    /// an exact Ornstein-Uhlenbeck friction and thermal kick step.
+    Langevin { temperature: Real, friction: Real },
+
+    /// Overdamped Brownian dynamics.
+    /// The inertia is neglected: the bodies move by the drift of the applied force
+    /// plus a random displacement. The drag force of the body and the integrator
+    /// of the space are not used in this mode.
+    Brownian { temperature: Real, friction: Real },
+}
+
+impl Thermostat {
+    pub(crate) fn integrate<const D: usize>(
+        &self,
+        body: &mut Body<D>,
+        dt: Real,
+        integrator: Integrator,
+        fields: &[Box<dyn ForceField<D>>],
+        random: &mut Random,
+    ) {
+        match *self {
+            Thermostat::Langevin { temperature, friction } => {
+                body.integrate(dt, integrator, fields);
+
+                let damping = exp(-friction * dt);
+                let noise_scale = ((1.0 - damping * damping) * temperature * body.inverse_mass()).sqrt();
+                body.set_velocity(body.velocity() * damping + random.normal_vector() * noise_scale);
+            },
+            Thermostat::Brownian { temperature, friction } => {
+                let mobility = body.inverse_mass() / friction;
+                let field_force = fields.iter()
+                    .fold(Vector::zero(), |total, field| total + field.force(body, body.position(), body.velocity()));
+                let drift = (body.force() + field_force) * (mobility * dt);
+                let diffusion = random.normal_vector() * (2.0 * temperature * mobility * dt).sqrt();
+                let displacement = drift + diffusion;
+
+                body.set_velocity(if dt > 0.0 { displacement / dt } else { Vector::zero() });
+                body.displace(displacement);
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::space::{Space};
+    use super::super::math::Vec2;
+    use std::time::Duration;
+
+    /// Bodies without shape do not collide, so only the thermostat changes their velocities.
+    fn thermal_space(thermostat: Thermostat, seed: u64) -> Space {
+        let mut space = Space::new(100.0, 100.0).with_thermostat(thermostat, seed);
+        for _ in 0..400 {
+            let mut body = Body::new(Vec2::xy(50.0, 50.0));
+            body.set_mass(2.0);
+            space.add(body);
+        }
+        space
+    }
+
+    #[test]
+    fn langevin_reaches_temperature() {
+        let mut space = thermal_space(Thermostat::Langevin { temperature: 3.0, friction: 5.0 }, 1);
+        let mut temperature = 0.0;
+        for step in 0..400 {
+            space.update(Duration::from_millis(10));
+            if step >= 200 {
+                temperature += space.kinetic_temperature() / 200.0;
+            }
+        }
+        assert!((temperature - 3.0).abs() < 0.2, "temperature: {}", temperature);
+    }
+
+    #[test]
+    fn seeded_runs_are_reproducible() {
+        let thermostat = Thermostat::Brownian { temperature: 1.0, friction: 2.0 };
+        let mut space1 = thermal_space(thermostat, 99);
+        let mut space2 = thermal_space(thermostat, 99);
+        for _ in 0..50 {
+            space1.update(Duration::from_millis(10));
+            space2.update(Duration::from_millis(10));
+        }
+        for (b1, b2) in space1.bodies().zip(space2.bodies()) {
+            assert_eq!(b1.position(), b2.position());
+        }
+    }
+}